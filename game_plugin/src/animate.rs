@@ -1,5 +1,5 @@
-use crate::board::{Board, Cauldron, Score};
-use crate::matcher::{Collectable, Slot};
+use crate::board::{Board, Cauldron, Score, MAX_COMBO};
+use crate::matcher::Collectable;
 use crate::{GameState, SystemLabels};
 use bevy::prelude::*;
 use std::cmp::min;
@@ -27,6 +27,8 @@ pub struct Move {
     pub process_for_cauldron: bool,
     pub throw_in_cauldron: bool,
     pub throw_away: bool,
+    /// Multiplies the coins for this collectable. Counts the waves of the cascade it was part of.
+    pub combo: usize,
     origin: Option<Vec2>,
 }
 
@@ -36,49 +38,58 @@ pub struct Animate {
 }
 
 impl Move {
-    pub fn move_to_slot(slot: &Slot) -> Self {
+    pub fn move_to(goal: Vec2) -> Self {
         Move {
-            goal: Vec2::new(
-                slot.column as f32 * 64. + 32. + 12.,
-                slot.row as f32 * 64. + 32. + 12.,
-            ),
+            goal,
             speed: 256.,
             process_for_cauldron: false,
             throw_in_cauldron: false,
             throw_away: false,
+            combo: 1,
             origin: None,
         }
     }
 
-    pub fn process() -> Self {
+    pub fn process(combo: usize) -> Self {
         Move {
             goal: Vec2::new(800. - 132., 300.),
             speed: 384.,
             process_for_cauldron: true,
             throw_in_cauldron: false,
             throw_away: false,
+            combo,
             origin: None,
         }
     }
 
-    pub fn throw_in_cauldron() -> Self {
+    pub fn throw_in_cauldron(combo: usize) -> Self {
         Move {
             goal: Vec2::new(800. - 132., 128. + 8.),
             speed: 256.0,
             process_for_cauldron: false,
             throw_in_cauldron: true,
             throw_away: false,
+            combo,
             origin: Some(Vec2::new(700., 300.)),
         }
     }
 
-    pub fn throw_away() -> Self {
+    /// Like throwing in the cauldron, but onto the shelf in the HUD
+    pub fn put_on_shelf(combo: usize) -> Self {
+        Move {
+            goal: Vec2::new(670., 215.),
+            ..Move::throw_in_cauldron(combo)
+        }
+    }
+
+    pub fn throw_away(combo: usize) -> Self {
         Move {
             goal: Vec2::new(850., 300.),
             speed: 256.0,
             process_for_cauldron: false,
             throw_in_cauldron: false,
             throw_away: true,
+            combo,
             origin: Some(Vec2::new(700., 300.)),
         }
     }
@@ -105,21 +116,23 @@ fn move_collectables(
         if diff.length() < (delta * animate.speed) {
             transform.translation.x = animate.goal.x;
             transform.translation.y = animate.goal.y;
+            let combo = animate.combo;
             if animate.process_for_cauldron {
                 animations.remove(0);
-                let current = cauldron.content.get(collectable).unwrap_or(&0);
-                if !cauldron.recipe.ingredients.iter().any(|ingredient| {
-                    &ingredient.collectable == collectable && &ingredient.amount > current
-                }) {
-                    animations.insert(0, Move::throw_away());
+                if !cauldron.accepts(collectable) {
+                    animations.insert(0, Move::throw_away(combo));
                     continue;
                 }
-                animations.insert(0, Move::throw_in_cauldron());
-                *cauldron.content.entry(collectable.clone()).or_insert(0) += 1;
+                if cauldron.needs(collectable) {
+                    animations.insert(0, Move::throw_in_cauldron(combo));
+                } else {
+                    animations.insert(0, Move::put_on_shelf(combo));
+                }
+                cauldron.add(collectable.clone(), combo);
             } else if animate.throw_in_cauldron {
                 commands.entity(entity).despawn();
             } else if animate.throw_away {
-                score.money += 1;
+                score.money += combo.min(MAX_COMBO);
                 commands.entity(entity).despawn();
             } else {
                 if animations.len() == 1 {
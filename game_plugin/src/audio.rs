@@ -1,4 +1,5 @@
 use crate::loading::AudioAssets;
+use crate::save::SaveData;
 use crate::GameState;
 use bevy::prelude::*;
 use bevy_kira_audio::{Audio, AudioChannel, AudioPlugin, AudioSource};
@@ -14,8 +15,10 @@ impl Plugin for InternalAudioPlugin {
         })
         .add_plugin(AudioPlugin)
         .add_event::<AudioEffect>()
-        .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(start_audio.system()))
-        .add_system(play_effect.system());
+        .init_resource::<Ducking>()
+        .add_system_set(SystemSet::on_exit(GameState::Loading).with_system(start_audio.system()))
+        .add_system(play_effect.system())
+        .add_system(set_volume.system());
     }
 }
 
@@ -25,18 +28,45 @@ struct AudioChannels {
     cooking: AudioChannel,
 }
 
+/// Music and cooking are played quieter while this is set, e.g. while the game is paused
+#[derive(Default)]
+pub struct Ducking(pub bool);
+
+/// Volume of the music and cooking channels while they are ducked
+const DUCKED_VOLUME: f32 = 0.3;
+
 pub struct AudioEffect {
     pub handle: Handle<AudioSource>,
 }
 
 fn start_audio(audio: Res<Audio>, channels: Res<AudioChannels>, audio_assets: Res<AudioAssets>) {
-    audio.set_volume_in_channel(0.4, &channels.effects);
-    audio.set_volume_in_channel(0.3, &channels.background);
-    audio.set_volume_in_channel(1.0, &channels.cooking);
     audio.play_looped_in_channel(audio_assets.background.clone(), &channels.background);
     audio.play_looped_in_channel(audio_assets.cooking.clone(), &channels.cooking);
 }
 
+/// Follow the volume settings, e.g. while they are changed on the settings screen
+fn set_volume(
+    audio: Res<Audio>,
+    channels: Res<AudioChannels>,
+    save: Res<SaveData>,
+    ducking: Res<Ducking>,
+) {
+    if !save.is_changed() && !ducking.is_changed() {
+        return;
+    }
+    let settings = &save.settings;
+    let ducked = if ducking.0 { DUCKED_VOLUME } else { 1. };
+    audio.set_volume_in_channel(settings.volume(settings.effects_volume), &channels.effects);
+    audio.set_volume_in_channel(
+        settings.volume(settings.music_volume) * ducked,
+        &channels.background,
+    );
+    audio.set_volume_in_channel(
+        settings.volume(settings.cooking_volume) * ducked,
+        &channels.cooking,
+    );
+}
+
 fn play_effect(
     mut events: EventReader<AudioEffect>,
     audio: Res<Audio>,
@@ -1,11 +1,19 @@
 use crate::animate::{Animate, Move};
 use crate::audio::AudioEffect;
-use crate::hud::FinishedRecipe;
-use crate::loading::{AudioAssets, RawTextureAssets, TextureAssets};
-use crate::matcher::{Collectable, Pattern, Slot, SlotContent};
+use crate::hud::{BoardShuffled, Combo, FinishedRecipe, SpoiledRecipe};
+use crate::loading::{AudioAssets, RawTextureAssets, RecipeAssets, TextureAssets};
+use crate::lost::{EndRun, RunOutcome};
+use crate::matcher::{Collectable, Obstacle, Slot, Special, Tile};
+use crate::mode::{RunLimit, RunSettings};
+use crate::random::GameRng;
+use crate::recipe::{Recipe, RecipeBook};
+use crate::replay::Replays;
+use crate::save::SaveData;
+use crate::shop::Upgrades;
+use crate::simulation::{BoardEvent, Grid, Layout};
 use crate::{GameState, SystemLabels};
 use bevy::prelude::*;
-use rand::{random, thread_rng, Rng};
+use rand::Rng;
 use std::collections::HashMap;
 use std::ops::Deref;
 
@@ -13,17 +21,19 @@ pub struct BoardPlugin;
 
 impl Plugin for BoardPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.insert_resource(Cauldron::new())
+        app.init_resource::<Cauldron>()
+            .init_resource::<RunClock>()
+            .init_resource::<ObstacleMaterials>()
             .insert_resource(Score { money: 0 })
             .insert_resource::<Selected>(None)
-            .add_system_set(
-                SystemSet::on_enter(GameState::Menu)
-                    .with_system(setup_shop.system())
-                    .with_system(set_camera.system()),
-            )
+            .insert_resource(HammerArmed(false))
+            .add_event::<PlayerSwitch>()
+            .add_event::<PickSlot>()
+            .add_system_set(SystemSet::on_exit(GameState::Loading).with_system(setup_shop.system()))
             .add_system_set(
                 SystemSet::on_enter(GameState::Playing)
-                    .with_system(prepare_board.system())
+                    .with_system(start_run.system().label(SystemLabels::StartRun))
+                    .with_system(prepare_board.system().after(SystemLabels::StartRun))
                     .with_system(show_scroll.system()),
             )
             .add_system_set(
@@ -38,7 +48,8 @@ impl Plugin for BoardPlugin {
                         user_selection
                             .system()
                             .label(SystemLabels::UserInput)
-                            .after(SystemLabels::MatchPatterns),
+                            .after(SystemLabels::MatchPatterns)
+                            .after(SystemLabels::ReadInput),
                     )
                     .with_system(check_possibilities.system().before(SystemLabels::UserInput))
                     .with_system(
@@ -46,6 +57,12 @@ impl Plugin for BoardPlugin {
                             .system()
                             .after(SystemLabels::Animate),
                     )
+                    .with_system(
+                        spoil_recipe
+                            .system()
+                            .after(SystemLabels::ReadInput)
+                            .before(SystemLabels::UserInput),
+                    )
                     .with_system(lose.system()),
             )
             .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(reset.system()));
@@ -54,68 +71,302 @@ impl Plugin for BoardPlugin {
 
 pub type Selected = Option<Slot>;
 
+/// Picking a slot smashes it with a hammer instead of selecting it
+pub struct HammerArmed(pub bool);
+
+/// The player picked a slot with the mouse, keyboard or gamepad
+///
+/// Picking a neighbor of the selected slot switches both.
+pub struct PickSlot {
+    pub slot: Slot,
+}
+
+/// The player switched two tiles
+pub struct PlayerSwitch {
+    pub one: Slot,
+    pub two: Slot,
+}
+
+#[derive(Clone)]
 pub struct Score {
     pub money: usize,
 }
 
-#[derive(Debug)]
-pub struct Cauldron {
+/// Seconds the current run has been played. Orders expire by it.
+///
+/// It is kept apart from the [Cauldron], so the cauldron only changes when its orders do.
+/// While a replay plays, the replay sets the clock.
+#[derive(Clone, Default)]
+pub struct RunClock {
+    pub seconds: f32,
+}
+
+/// Seconds a customer waits for an order whose recipe has no time limit
+const PATIENCE: f32 = 120.;
+/// The highest multiplier a combo gives the coins of one collectable
+pub const MAX_COMBO: usize = 4;
+/// Customers waiting at the same time. The scroll has room for at most four orders.
+pub const MIN_ORDERS: usize = 2;
+pub const MAX_ORDERS: usize = 4;
+
+/// A customer waiting for a potion
+#[derive(Clone, Debug)]
+pub struct Order {
     pub recipe: Recipe,
     pub content: HashMap<Collectable, usize>,
+    /// Extra ingredients paid for, because they were delivered in a combo
+    pub combo_bonus: usize,
+    /// Time of the run at which the customer leaves without paying
+    pub deadline: f32,
+}
+
+impl Order {
+    /// A new order, placed at the given time of the run
+    pub fn new(recipe: Recipe, now: f32) -> Self {
+        Order {
+            deadline: now + recipe.time_limit.unwrap_or(PATIENCE),
+            recipe,
+            content: HashMap::new(),
+            combo_bonus: 0,
+        }
+    }
+
+    pub fn seconds_left(&self, now: f32) -> f32 {
+        (self.deadline - now).max(0.)
+    }
+
+    pub fn needs(&self, collectable: &Collectable) -> bool {
+        self.missing(collectable) > 0
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.recipe
+            .ingredients
+            .iter()
+            .all(|ingredient| self.missing(&ingredient.collectable) == 0)
+    }
+
+    /// How many more of the collectable the order needs
+    fn missing(&self, collectable: &Collectable) -> usize {
+        let needed: usize = self
+            .recipe
+            .ingredients
+            .iter()
+            .filter(|ingredient| &ingredient.collectable == collectable)
+            .map(|ingredient| ingredient.amount)
+            .sum();
+        needed.saturating_sub(*self.content.get(collectable).unwrap_or(&0))
+    }
+}
+
+/// Ingredients the shelf holds without any upgrade
+const SHELF_CAPACITY: usize = 4;
+
+/// Keeps ingredients that no order needed for the orders to come
+#[derive(Clone, Debug)]
+pub struct Shelf {
+    items: HashMap<Collectable, usize>,
+    capacity: usize,
+}
+
+impl Shelf {
+    fn new(capacity: usize) -> Self {
+        Shelf {
+            items: HashMap::new(),
+            capacity,
+        }
+    }
+
+    pub fn count(&self, collectable: &Collectable) -> usize {
+        *self.items.get(collectable).unwrap_or(&0)
+    }
+
+    /// Number of ingredients on the shelf
+    pub fn stored(&self) -> usize {
+        self.items.values().sum()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn has_room(&self) -> bool {
+        self.stored() < self.capacity
+    }
+
+    /// Store the collectable. Returns false if the shelf is full.
+    fn put(&mut self, collectable: Collectable) -> bool {
+        if !self.has_room() {
+            return false;
+        }
+        *self.items.entry(collectable).or_insert(0) += 1;
+        true
+    }
+
+    /// Take up to `wanted` of the collectable from the shelf. Returns how many were taken.
+    fn take(&mut self, collectable: &Collectable, wanted: usize) -> usize {
+        let count = match self.items.get_mut(collectable) {
+            Some(count) => count,
+            None => return 0,
+        };
+        let taken = wanted.min(*count);
+        *count -= taken;
+        if *count == 0 {
+            self.items.remove(collectable);
+        }
+        taken
+    }
+}
+
+/// The open orders of the customers and what was brewed for them
+#[derive(Clone, Debug)]
+pub struct Cauldron {
+    /// The oldest order first. Ingredients go to the first order that still needs them.
+    pub orders: Vec<Order>,
+    /// Ingredients that no order needed. New orders take what they need from here.
+    pub shelf: Shelf,
     pub finished_recipes: usize,
+    /// Percent added to the reward of every potion
+    reward_bonus: usize,
 }
 
 impl Cauldron {
-    pub fn new() -> Self {
+    pub fn new(recipes: Vec<Recipe>) -> Self {
         Cauldron {
-            recipe: Recipe::build_random(),
-            content: HashMap::new(),
+            orders: recipes
+                .into_iter()
+                .map(|recipe| Order::new(recipe, 0.))
+                .collect(),
+            shelf: Shelf::new(SHELF_CAPACITY),
             finished_recipes: 0,
+            reward_bonus: 0,
         }
     }
 
-    pub fn new_recipe(&mut self) {
-        self.recipe = Recipe::build_random();
-        self.content = HashMap::new();
+    /// Apply the upgrades bought in the shop
+    pub fn with_upgrades(self, upgrades: &Upgrades) -> Self {
+        Cauldron {
+            reward_bonus: upgrades.reward_bonus(),
+            shelf: Shelf::new(SHELF_CAPACITY + upgrades.shelf_space()),
+            ..self
+        }
     }
-}
 
-#[derive(Debug)]
-pub struct Recipe {
-    pub ingredients: Vec<Ingredients>,
-    pub reward: usize,
-}
+    /// A new customer orders the recipe. The order takes what it needs from the shelf.
+    pub fn add_order(&mut self, recipe: Recipe, now: f32) {
+        let mut order = Order::new(recipe, now);
+        for ingredient in order.recipe.ingredients.clone() {
+            let missing = order.missing(&ingredient.collectable);
+            let taken = self.shelf.take(&ingredient.collectable, missing);
+            if taken > 0 {
+                *order.content.entry(ingredient.collectable).or_insert(0) += taken;
+            }
+        }
+        self.orders.push(order);
+    }
 
-impl Recipe {
-    pub fn build_random() -> Self {
-        // get three random collectables
-        let mut rng = thread_rng();
-        let mut collectables = vec![];
-        while collectables.len() < 3 {
-            let random = rng.gen::<Collectable>();
-            if collectables.contains(&random) {
-                continue;
+    /// Replace the oldest order with one for the given recipe
+    pub fn replace_first_order(&mut self, recipe: Recipe, now: f32) {
+        if !self.orders.is_empty() {
+            // what does not fit on the shelf is lost with the old order
+            let order = self.orders.remove(0);
+            for (collectable, count) in order.content {
+                for _ in 0..count {
+                    self.shelf.put(collectable.clone());
+                }
             }
-            collectables.push(random);
         }
-        let ingredients = collectables
+        self.add_order(recipe, now);
+        // the replacement keeps the place of the old order
+        let order = self.orders.pop().unwrap();
+        self.orders.insert(0, order);
+    }
+
+    /// The coins for brewing the order
+    ///
+    /// Every ingredient earns its share of the recipe's reward, multiplied by the combo it was
+    /// delivered in.
+    pub fn reward(&self, order: &Order) -> usize {
+        let ingredients: usize = order
+            .recipe
+            .ingredients
+            .iter()
+            .map(|ingredient| ingredient.amount)
+            .sum();
+        order.recipe.reward * (ingredients + order.combo_bonus) / ingredients.max(1)
+            * (100 + self.reward_bonus)
+            / 100
+    }
+
+    /// Any open order needs the collectable
+    pub fn needs(&self, collectable: &Collectable) -> bool {
+        self.orders.iter().any(|order| order.needs(collectable))
+    }
+
+    /// The collectable is needed by an order or fits on the shelf
+    pub fn accepts(&self, collectable: &Collectable) -> bool {
+        self.needs(collectable) || self.shelf.has_room()
+    }
+
+    /// Put the collectable into the first order that needs it, or on the shelf
+    pub fn add(&mut self, collectable: Collectable, combo: usize) {
+        match self
+            .orders
+            .iter_mut()
+            .find(|order| order.needs(&collectable))
+        {
+            Some(order) => {
+                *order.content.entry(collectable).or_insert(0) += 1;
+                order.combo_bonus += combo.clamp(1, MAX_COMBO) - 1;
+            }
+            None => {
+                self.shelf.put(collectable);
+            }
+        }
+    }
+
+    /// Remove the completed orders from the queue
+    pub fn take_completed(&mut self) -> Vec<Order> {
+        let (completed, open) = self
+            .orders
+            .drain(..)
+            .partition(|order: &Order| order.is_complete());
+        self.orders = open;
+        completed
+    }
+
+    /// Remove the orders of the customers who ran out of patience
+    pub fn take_expired(&mut self, now: f32) -> Vec<Order> {
+        let (left, waiting) = self
+            .orders
             .drain(..)
-            .map(|collectable| Ingredients {
-                amount: rng.gen_range(4..8),
-                collectable,
-            })
-            .collect();
-        Recipe {
-            ingredients,
-            reward: 77,
+            .partition(|order: &Order| order.deadline <= now);
+        self.orders = waiting;
+        left
+    }
+
+    /// The collectables that the orders still need, the ones of the oldest order first
+    pub fn missing_ingredients(&self) -> Vec<Collectable> {
+        let mut missing = vec![];
+        for order in self.orders.iter() {
+            for ingredient in order.recipe.ingredients.iter() {
+                if order.needs(&ingredient.collectable)
+                    && !missing.contains(&ingredient.collectable)
+                {
+                    missing.push(ingredient.collectable.clone());
+                }
+            }
         }
+
+        missing
     }
 }
 
-#[derive(Debug)]
-pub struct Ingredients {
-    pub amount: usize,
-    pub collectable: Collectable,
+/// Empty until a run fills it with the orders of its settings
+impl Default for Cauldron {
+    fn default() -> Self {
+        Cauldron::new(vec![])
+    }
 }
 
 fn setup_shop(
@@ -157,185 +408,309 @@ fn show_scroll(
         .insert(Scroll);
 }
 
-fn set_camera(mut commands: Commands) {
-    commands.spawn_bundle(OrthographicCameraBundle {
-        transform: Transform::from_translation(Vec3::new(400., 300., 999.9)),
-        ..OrthographicCameraBundle::new_2d()
-    });
+/// How the collectables of the rules are drawn
+pub trait CollectableLook {
+    fn get_texture(&self, assets: &TextureAssets) -> Handle<TextureAtlas>;
+    fn get_animation(&self) -> Animate;
 }
 
-fn prepare_board(mut commands: Commands, textures: Res<TextureAssets>) {
-    let mut board = Board {
-        height: 9,
-        width: 8,
-        animating: true,
-        slots: vec![],
-    };
+impl CollectableLook for Collectable {
+    fn get_texture(&self, assets: &TextureAssets) -> Handle<TextureAtlas> {
+        match self {
+            &Collectable::Eye => assets.eye.clone(),
+            &Collectable::Tongue => assets.tongue.clone(),
+            &Collectable::Frog => assets.frog.clone(),
+            &Collectable::Heart => assets.heart.clone(),
+            &Collectable::Spider => assets.spider.clone(),
+            &Collectable::Jar => assets.jar.clone(),
+            &Collectable::Teeth => assets.teeth.clone(),
+            &Collectable::Yellow => assets.yellow.clone(),
+            &Collectable::Red => assets.red.clone(),
+            &Collectable::Green => assets.green.clone(),
+        }
+    }
 
-    let animation_offset = board.height as f32 * 64.;
-    for column_index in 0..board.width {
-        let mut column = vec![];
-        for row_index in 0..board.height {
-            let goal = Vec2::new(
-                column_index as f32 * 64. + 32. + 12.,
-                row_index as f32 * 64. + 32. + 12.,
-            );
-            let slot = Slot {
-                row: row_index,
-                column: column_index,
-            };
-            let slot_content =
-                drop_random_collectable(&mut commands, goal, animation_offset, slot, &textures);
-            column.push(slot_content);
+    fn get_animation(&self) -> Animate {
+        match self {
+            &Collectable::Eye => Animate {
+                frames: 6,
+                loop_animation: false,
+            },
+            &Collectable::Tongue => Animate {
+                frames: 6,
+                loop_animation: true,
+            },
+            &Collectable::Frog => Animate {
+                frames: 6,
+                loop_animation: false,
+            },
+            &Collectable::Heart => Animate {
+                frames: 6,
+                loop_animation: true,
+            },
+            &Collectable::Spider => Animate {
+                frames: 6,
+                loop_animation: false,
+            },
+            &Collectable::Jar => Animate {
+                frames: 4,
+                loop_animation: true,
+            },
+            &Collectable::Teeth => Animate {
+                frames: 4,
+                loop_animation: true,
+            },
+            &Collectable::Yellow => Animate {
+                frames: 6,
+                loop_animation: true,
+            },
+            &Collectable::Red => Animate {
+                frames: 6,
+                loop_animation: true,
+            },
+            &Collectable::Green => Animate {
+                frames: 6,
+                loop_animation: true,
+            },
         }
-        board.slots.push(column);
     }
-    commands.insert_resource(board);
 }
 
-fn take_patterns(mut board: ResMut<Board>, mut commands: Commands, textures: Res<TextureAssets>) {
-    if board.animating {
-        return;
+/// How the tiles of the rules are drawn
+pub trait TileLook {
+    /// The sprite of the tile while it is not animated
+    fn sprite(&self) -> TextureAtlasSprite;
+}
+
+impl TileLook for Tile {
+    fn sprite(&self) -> TextureAtlasSprite {
+        TextureAtlasSprite {
+            color: self
+                .special
+                .map(|special| special_color(&special))
+                .unwrap_or(Color::WHITE),
+            ..TextureAtlasSprite::default()
+        }
     }
-    let mut patterns = board.find_patterns();
-    if patterns.is_empty() {
-        return;
+}
+
+/// Tint of the tiles with the special
+fn special_color(special: &Special) -> Color {
+    match special {
+        Special::RowClearer => Color::rgb(0.6, 0.8, 1.),
+        Special::ColumnClearer => Color::rgb(0.6, 1., 0.6),
+        Special::Bomb => Color::rgb(1., 0.6, 0.4),
+        Special::ColourWipe => Color::rgb(0.9, 0.5, 1.),
     }
+}
 
-    let mut pattern_slots = patterns
-        .drain(..)
-        .flat_map(|pattern| match pattern {
-            Pattern::Line { slots } => slots,
-        })
-        .collect::<Vec<Slot>>();
-    pattern_slots.sort();
-    pattern_slots.dedup();
+/// Tint of the obstacles on top of their slots
+fn obstacle_color(obstacle: &Obstacle) -> Color {
+    match obstacle {
+        Obstacle::Ice => Color::rgba(0.7, 0.9, 1., 0.5),
+        Obstacle::Chain => Color::rgba(0.3, 0.25, 0.2, 0.6),
+        Obstacle::Stone => Color::rgb(0.45, 0.45, 0.5),
+    }
+}
 
-    if pattern_slots.is_empty() {
-        return;
+pub struct ObstacleMaterials {
+    materials: HashMap<Obstacle, Handle<ColorMaterial>>,
+}
+
+impl FromWorld for ObstacleMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        ObstacleMaterials {
+            materials: [Obstacle::Ice, Obstacle::Chain, Obstacle::Stone]
+                .iter()
+                .map(|obstacle| (*obstacle, materials.add(obstacle_color(obstacle).into())))
+                .collect(),
+        }
     }
+}
 
-    let entities: Vec<SlotContent> = pattern_slots
-        .iter()
-        .map(|slot| board.get_content(slot))
-        .collect();
+fn prepare_board(
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    obstacle_materials: Res<ObstacleMaterials>,
+    mut rng: ResMut<GameRng>,
+    settings: Res<RunSettings>,
+) {
+    let (grid, events) = Grid::new(
+        settings.layout.clone(),
+        settings.collectables.clone(),
+        settings.weights.clone(),
+        &mut rng.board,
+    );
+    let mut board = Board {
+        geometry: BoardGeometry::new(grid.layout()),
+        grid,
+        entities: HashMap::default(),
+        obstacles: HashMap::default(),
+        animating: true,
+    };
+    board.spawn_obstacles(&mut commands, &obstacle_materials);
+    board.render(events, &mut commands, &textures);
+    commands.insert_resource(board);
+}
 
-    for SlotContent {
-        entity,
-        collectable,
-    } in entities
-    {
-        commands
-            .entity(entity)
-            .remove::<Slot>()
-            .insert(collectable.get_animation())
-            .insert(vec![Move::process()]);
+fn take_patterns(
+    mut board: ResMut<Board>,
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    mut rng: ResMut<GameRng>,
+    mut combos: EventWriter<Combo>,
+) {
+    if board.animating || board.grid.is_settled() {
+        return;
     }
 
-    let slots_to_animate = board.remove_slots(pattern_slots, &mut commands, &textures);
-    for slot in slots_to_animate {
-        let content = board.get_content(&slot);
-        commands
-            .entity(content.entity)
-            .insert(vec![Move::move_to_slot(&slot)])
-            .insert(slot);
+    let events = board.grid.take_patterns(&mut rng.board);
+    board.render(events, &mut commands, &textures);
+    if board.grid.cascade() > 1 {
+        combos.send(Combo {
+            multiplier: board.grid.cascade(),
+        });
     }
 }
 
 fn user_selection(
     mut commands: Commands,
     mut selection: ResMut<Selected>,
-    windows: Res<Windows>,
+    mut picks: EventReader<PickSlot>,
     mut effects: EventWriter<AudioEffect>,
     audio: Res<AudioAssets>,
-    mouse_buttons: Res<Input<MouseButton>>,
     mut board: ResMut<Board>,
+    textures: Res<TextureAssets>,
+    limit: Res<RunLimit>,
+    hammer: Res<HammerArmed>,
+    mut switches: EventWriter<PlayerSwitch>,
 ) {
-    if !board.animating && mouse_buttons.just_pressed(MouseButton::Left) {
-        let window = windows.get_primary().expect("No primary window found");
-        if let Some(position) = window.cursor_position() {
-            if position.x < 12. {
-                return;
-            }
-            let column = ((position.x - 12.) / 64.) as usize;
-            let row = (position.y / 64.) as usize;
-            let slot = Slot { row, column };
-            if slot.row >= board.height || slot.column >= board.width {
-                return;
-            }
-            let tile_two = board.get_content(&slot);
-            if let Some(one) = selection.deref() {
-                let neighbors = board.neighbors(one);
-                let tile_one = board.get_content(one);
-                if !neighbors.contains(&slot) {
-                    commands
-                        .entity(tile_one.entity)
-                        .remove::<Animate>()
-                        .insert(TextureAtlasSprite::default());
-                    commands
-                        .entity(tile_two.entity)
-                        .insert(tile_two.collectable.get_animation());
-                    effects.send(AudioEffect {
-                        handle: audio.select.clone(),
-                    });
-                    *selection = Some(slot);
-                    return;
-                }
-                if !board.has_pattern_after_switch(one, &slot) {
-                    effects.send(AudioEffect {
-                        handle: audio.no.clone(),
-                    });
-                    return;
-                }
-                effects.send(AudioEffect {
-                    handle: audio.select.clone(),
-                });
-                commands
-                    .entity(tile_one.entity)
-                    .insert(vec![Move::move_to_slot(&slot)]);
-                commands
-                    .entity(tile_two.entity)
-                    .insert(vec![Move::move_to_slot(one)]);
-                board.switch(one, &slot, &mut commands);
-                commands
-                    .entity(tile_one.entity)
-                    .remove::<Animate>()
-                    .insert(TextureAtlasSprite::default());
-                *selection = None;
-            } else {
-                effects.send(AudioEffect {
-                    handle: audio.select.clone(),
-                });
-                commands
-                    .entity(tile_two.entity)
-                    .insert(tile_two.collectable.get_animation());
-                *selection = Some(slot);
-            }
+    // read the picks in any case, so they don't pile up until the board takes picks again
+    let picked = picks.iter().last().map(|pick| pick.slot.clone());
+    if limit.is_reached() || hammer.0 {
+        return;
+    }
+    let slot = match picked {
+        Some(slot) if !board.animating && board.grid.has_tile(&slot) => slot,
+        _ => return,
+    };
+    let tile_two = board.entity(&slot);
+    if let Some(one) = selection.deref() {
+        let neighbors = board.grid.neighbors(one);
+        let tile_one = board.entity(one);
+        if one == &slot {
+            // picking the selected tile again deselects it
+            commands
+                .entity(tile_one)
+                .remove::<Animate>()
+                .insert(board.grid.get(one).sprite());
+            *selection = None;
+            return;
+        }
+        if !neighbors.contains(&slot) {
+            commands
+                .entity(tile_one)
+                .remove::<Animate>()
+                .insert(board.grid.get(one).sprite());
+            commands
+                .entity(tile_two)
+                .insert(board.grid.get(&slot).collectable.get_animation());
+            effects.send(AudioEffect {
+                handle: audio.select.clone(),
+            });
+            *selection = Some(slot);
+            return;
         }
+        if !board.grid.has_pattern_after_switch(one, &slot) {
+            effects.send(AudioEffect {
+                handle: audio.no.clone(),
+            });
+            // the tile might have been dragged towards the other one
+            commands
+                .entity(tile_one)
+                .insert(vec![Move::move_to(board.geometry.position(one))]);
+            return;
+        }
+        effects.send(AudioEffect {
+            handle: audio.select.clone(),
+        });
+        let event = board.grid.switch(one, &slot);
+        board.render(vec![event], &mut commands, &textures);
+        // the first tile is in the second slot now
+        commands
+            .entity(tile_one)
+            .remove::<Animate>()
+            .insert(board.grid.get(&slot).sprite());
+        switches.send(PlayerSwitch {
+            one: one.clone(),
+            two: slot,
+        });
+        *selection = None;
+    } else {
+        effects.send(AudioEffect {
+            handle: audio.select.clone(),
+        });
+        commands
+            .entity(tile_two)
+            .insert(board.grid.get(&slot).collectable.get_animation());
+        *selection = Some(slot);
     }
 }
 
+fn start_run(
+    mut cauldron: ResMut<Cauldron>,
+    mut clock: ResMut<RunClock>,
+    mut score: ResMut<Score>,
+    mut rng: ResMut<GameRng>,
+    recipe_assets: Res<RecipeAssets>,
+    recipe_books: Res<Assets<RecipeBook>>,
+    settings: Res<RunSettings>,
+    save: Res<SaveData>,
+) {
+    rng.next_run();
+    let recipes = (0..settings.orders.clamp(MIN_ORDERS, MAX_ORDERS))
+        .map(|_| {
+            Recipe::next(
+                recipe_books.get(&recipe_assets.book),
+                &settings.collectables,
+                &settings.weights,
+                0,
+                &mut rng.recipes,
+            )
+        })
+        .collect();
+    *cauldron = Cauldron::new(recipes).with_upgrades(&save.upgrades);
+    clock.seconds = 0.;
+    score.money = 0;
+}
+
 fn check_recipe_completion(
     mut cauldron: ResMut<Cauldron>,
     mut finished_recipe: EventWriter<FinishedRecipe>,
     mut score: ResMut<Score>,
     mut audio_effect: EventWriter<AudioEffect>,
     audio_assets: Res<AudioAssets>,
+    mut rng: ResMut<GameRng>,
+    recipe_assets: Res<RecipeAssets>,
+    recipe_books: Res<Assets<RecipeBook>>,
+    settings: Res<RunSettings>,
+    clock: Res<RunClock>,
 ) {
-    if cauldron.is_changed() {
-        for ingredient in cauldron.recipe.ingredients.iter() {
-            if let Some(current) = cauldron.content.get(&ingredient.collectable) {
-                if current < &ingredient.amount {
-                    return;
-                }
-            } else {
-                return;
-            }
-        }
-
-        score.money += cauldron.recipe.reward;
+    if !cauldron.is_changed() || !cauldron.orders.iter().any(|order| order.is_complete()) {
+        return;
+    }
+    for order in cauldron.take_completed() {
+        score.money += cauldron.reward(&order);
         cauldron.finished_recipes += 1;
-        cauldron.new_recipe();
+        let recipe = Recipe::next(
+            recipe_books.get(&recipe_assets.book),
+            &settings.collectables,
+            &settings.weights,
+            cauldron.finished_recipes,
+            &mut rng.recipes,
+        );
+        cauldron.add_order(recipe, clock.seconds);
         audio_effect.send(AudioEffect {
             handle: audio_assets.potion_complete.clone(),
         });
@@ -343,508 +718,346 @@ fn check_recipe_completion(
     }
 }
 
-fn lose(
-    input: Res<Input<KeyCode>>,
-    mut state: ResMut<State<GameState>>,
+/// Count the time of the run and replace the orders of customers who ran out of patience
+fn spoil_recipe(
+    time: Res<Time>,
+    mut clock: ResMut<RunClock>,
+    mut cauldron: ResMut<Cauldron>,
+    mut spoiled_recipe: EventWriter<SpoiledRecipe>,
     mut audio_effect: EventWriter<AudioEffect>,
     audio_assets: Res<AudioAssets>,
+    mut rng: ResMut<GameRng>,
+    recipe_assets: Res<RecipeAssets>,
+    recipe_books: Res<Assets<RecipeBook>>,
+    settings: Res<RunSettings>,
+    replays: Res<Replays>,
 ) {
-    if input.just_pressed(KeyCode::R) {
+    if !replays.is_playing() {
+        clock.seconds += time.delta_seconds();
+    }
+    let now = clock.seconds;
+    if !cauldron.orders.iter().any(|order| order.deadline <= now) {
+        return;
+    }
+    for _ in cauldron.take_expired(now) {
+        let recipe = Recipe::next(
+            recipe_books.get(&recipe_assets.book),
+            &settings.collectables,
+            &settings.weights,
+            cauldron.finished_recipes,
+            &mut rng.recipes,
+        );
+        cauldron.add_order(recipe, now);
         audio_effect.send(AudioEffect {
-            handle: audio_assets.lost.clone(),
+            handle: audio_assets.no.clone(),
+        });
+        spoiled_recipe.send(SpoiledRecipe);
+    }
+}
+
+fn lose(input: Res<Input<KeyCode>>, mut end_run: EventWriter<EndRun>) {
+    if input.just_pressed(KeyCode::R) {
+        end_run.send(EndRun {
+            outcome: RunOutcome::GaveUp,
         });
-        state.set(GameState::Lost).unwrap();
     }
 }
 
 fn check_possibilities(
-    board: Res<Board>,
-    mut state: ResMut<State<GameState>>,
-    mut audio_effect: EventWriter<AudioEffect>,
-    audio_assets: Res<AudioAssets>,
+    mut commands: Commands,
+    mut board: ResMut<Board>,
+    textures: Res<TextureAssets>,
+    mut rng: ResMut<GameRng>,
+    settings: Res<RunSettings>,
+    mut score: ResMut<Score>,
+    mut shuffled: EventWriter<BoardShuffled>,
+    mut end_run: EventWriter<EndRun>,
 ) {
-    if board.is_changed() && !board.animating {
-        let mut board = board.clone();
-        for column in 0..(board.width - 1) {
-            for row in 0..(board.height - 1) {
-                let current = Slot { column, row };
-                let next_column = Slot {
-                    column: column + 1,
-                    row,
-                };
-                let next_row = Slot {
-                    column,
-                    row: row + 1,
-                };
-                if board.has_pattern_after_switch(&current, &next_column)
-                    || board.has_pattern_after_switch(&current, &next_row)
-                {
-                    return;
-                }
-            }
+    if !board.is_changed() || board.animating || board.grid.clone().has_possible_switch() {
+        return;
+    }
+    if let Some(cost) = settings.shuffle_cost.filter(|cost| cost <= &score.money) {
+        if board.shuffle(&mut rng.board, &mut commands, &textures) {
+            score.money -= cost;
+            shuffled.send(BoardShuffled { cost });
+            return;
         }
-        audio_effect.send(AudioEffect {
-            handle: audio_assets.lost.clone(),
-        });
-        state.set(GameState::Lost).unwrap();
     }
+    end_run.send(EndRun {
+        outcome: RunOutcome::NoSwitchLeft,
+    });
 }
 
+/// Tiles are squares of this size in pixels
+pub const TILE_SIZE: f32 = 64.;
+/// The largest board that fits next to the shelf
+pub const MAX_BOARD_WIDTH: usize = 8;
+pub const MAX_BOARD_HEIGHT: usize = 9;
+const BOARD_MARGIN: f32 = 12.;
+
+/// Converts between slots and positions in the world
+///
+/// Boards smaller than the largest one are centered in its place.
 #[derive(Clone, Debug)]
+pub struct BoardGeometry {
+    /// The lower left corner of the slot in the first row and column
+    origin: Vec2,
+}
+
+impl BoardGeometry {
+    pub fn new(layout: &Layout) -> Self {
+        let free_columns = MAX_BOARD_WIDTH as f32 - layout.width() as f32;
+        let free_rows = MAX_BOARD_HEIGHT as f32 - layout.height() as f32;
+        BoardGeometry {
+            origin: Vec2::new(
+                BOARD_MARGIN + free_columns * TILE_SIZE / 2.,
+                BOARD_MARGIN + free_rows * TILE_SIZE / 2.,
+            ),
+        }
+    }
+
+    /// The center of the slot
+    pub fn position(&self, slot: &Slot) -> Vec2 {
+        self.origin
+            + Vec2::new(
+                slot.column as f32 * TILE_SIZE + TILE_SIZE / 2.,
+                slot.row as f32 * TILE_SIZE + TILE_SIZE / 2.,
+            )
+    }
+
+    /// The slot at the position. It is not necessarily part of the grid.
+    pub fn slot(&self, position: Vec2) -> Option<Slot> {
+        let local = (position - self.origin) / TILE_SIZE;
+        if local.x < 0. || local.y < 0. {
+            return None;
+        }
+        Some(Slot {
+            row: local.y as usize,
+            column: local.x as usize,
+        })
+    }
+}
+
+/// Renders the [Grid] with one entity per collectable
 pub struct Board {
-    height: usize,
-    width: usize,
+    pub grid: Grid,
+    pub geometry: BoardGeometry,
+    entities: HashMap<Slot, Entity>,
+    obstacles: HashMap<Slot, Entity>,
     pub animating: bool,
-    slots: Vec<Vec<SlotContent>>,
 }
 
 impl Board {
-    pub fn find_patterns(&self) -> Vec<Pattern> {
-        let mut patterns = vec![];
-        patterns.append(&mut self.find_patterns_in_columns());
-        patterns.append(&mut self.find_patterns_in_rows());
+    pub fn entity(&self, slot: &Slot) -> Entity {
+        *self
+            .entities
+            .get(slot)
+            .expect("Every slot of the board should have an entity")
+    }
 
-        patterns
+    /// The most valuable switch on the board, preferring the given collectables
+    pub fn find_best_move(&self, preferred: &[Collectable]) -> Option<(Slot, Slot)> {
+        self.grid.clone().best_switch(preferred)
     }
 
-    pub fn remove_slots(
+    /// Rearrange the tiles so that a switch is possible. Returns false if that did not work out.
+    pub fn shuffle<R: Rng + ?Sized>(
         &mut self,
-        mut slots: Vec<Slot>,
+        rng: &mut R,
         commands: &mut Commands,
         textures: &TextureAssets,
-    ) -> Vec<Slot> {
-        slots.sort();
-        slots.reverse();
-
-        let mut slots_to_animate = vec![];
-
-        let mut column = slots.first().unwrap().column;
-        let mut row = slots.first().unwrap().row;
-        for slot in slots {
-            if column != slot.column {
-                for row in row..self.slots.get(column).unwrap().len() {
-                    slots_to_animate.push(Slot { row, column })
-                }
-                self.fill_column(column, commands, textures);
-                row = slot.row;
-                column = slot.column;
-            } else {
-                row = slot.row;
+    ) -> bool {
+        match self.grid.shuffle(rng) {
+            Some(events) => {
+                self.render(events, commands, textures);
+                true
             }
-            self.slots.get_mut(slot.column).unwrap().remove(slot.row);
-        }
-        for row in row..self.slots.get(column).unwrap().len() {
-            slots_to_animate.push(Slot { row, column })
+            None => false,
         }
-        self.fill_column(column, commands, textures);
+    }
 
-        slots_to_animate
+    /// Smash the tile or obstacle in the slot with a hammer
+    pub fn smash<R: Rng + ?Sized>(
+        &mut self,
+        slot: &Slot,
+        rng: &mut R,
+        commands: &mut Commands,
+        textures: &TextureAssets,
+    ) {
+        let events = self.grid.smash(slot, rng);
+        self.render(events, commands, textures);
     }
 
-    fn fill_column(&mut self, column: usize, commands: &mut Commands, textures: &TextureAssets) {
-        let full_rows = self.slots.get(column).unwrap().len();
-        let slots_to_drop = self.height - full_rows;
-        let mut new_content = vec![];
-        for row in full_rows..self.height {
-            let goal = Vec2::new(
-                column as f32 * 64. + 32. + 12.,
-                row as f32 * 64. + 32. + 12.,
-            );
-            let slot_content = drop_random_collectable(
+    /// Replace the grid and spawn new entities for all of its tiles and obstacles
+    ///
+    /// The entities of the old grid need to be despawned already.
+    pub fn restore(
+        &mut self,
+        grid: Grid,
+        commands: &mut Commands,
+        textures: &TextureAssets,
+        obstacle_materials: &ObstacleMaterials,
+    ) {
+        self.grid = grid;
+        self.entities.clear();
+        self.obstacles.clear();
+        for slot in self.grid.layout().open_slots() {
+            if !self.grid.has_tile(&slot) {
+                continue;
+            }
+            let tile = self.grid.get(&slot);
+            let entity = spawn_collectable(
                 commands,
-                goal,
-                slots_to_drop as f32 * 64.,
-                Slot { row, column },
+                slot.clone(),
+                tile.collectable.clone(),
+                &self.geometry,
+                0.,
                 textures,
             );
-            new_content.push(slot_content);
+            commands.entity(entity).insert(tile.sprite());
+            self.entities.insert(slot, entity);
         }
-        self.slots.get_mut(column).unwrap().append(&mut new_content);
-    }
-
-    pub fn get_content(&self, slot: &Slot) -> SlotContent {
-        self.slots
-            .get(slot.column)
-            .unwrap()
-            .get(slot.row)
-            .unwrap()
-            .clone()
+        self.spawn_obstacles(commands, obstacle_materials);
     }
 
-    pub fn switch(&mut self, one: &Slot, two: &Slot, commands: &mut Commands) {
-        let tile_one = self.get_content(one);
-        let tile_two = self.get_content(two);
-
-        commands
-            .entity(tile_one.entity)
-            .insert(vec![Move::move_to_slot(two)])
-            .insert(two.clone());
-        commands
-            .entity(tile_two.entity)
-            .insert(vec![Move::move_to_slot(one)])
-            .insert(one.clone());
-
-        self.switch_in_slots(one, tile_one, two, tile_two);
+    fn spawn_obstacles(&mut self, commands: &mut Commands, obstacle_materials: &ObstacleMaterials) {
+        for (slot, obstacle) in self.grid.obstacles() {
+            let position = self.geometry.position(&slot);
+            // stones take the place of a tile, the other obstacles cover it
+            let z = if obstacle == Obstacle::Stone { 5. } else { 6. };
+            let entity = commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite::new(Vec2::new(TILE_SIZE, TILE_SIZE)),
+                    material: obstacle_materials.materials[&obstacle].clone(),
+                    transform: Transform::from_translation(position.extend(z)),
+                    ..SpriteBundle::default()
+                })
+                .insert(obstacle)
+                .id();
+            self.obstacles.insert(slot, entity);
+        }
     }
 
-    fn switch_in_slots(
+    /// Update the entities according to what happened in the grid
+    pub fn render(
         &mut self,
-        one: &Slot,
-        tile_one: SlotContent,
-        two: &Slot,
-        tile_two: SlotContent,
+        events: Vec<BoardEvent>,
+        commands: &mut Commands,
+        textures: &TextureAssets,
     ) {
-        self.slots.get_mut(one.column).unwrap().remove(one.row);
-        self.slots
-            .get_mut(one.column)
-            .unwrap()
-            .insert(one.row, tile_two);
-        self.slots.get_mut(two.column).unwrap().remove(two.row);
-        self.slots
-            .get_mut(two.column)
-            .unwrap()
-            .insert(two.row, tile_one);
-    }
-
-    pub fn has_pattern_after_switch(&mut self, one: &Slot, two: &Slot) -> bool {
-        let tile_one = self.get_content(one);
-        let tile_two = self.get_content(two);
-        self.switch_in_slots(one, tile_one, two, tile_two);
-
-        let has_patterns = !self.find_patterns().is_empty();
-
-        let tile_one = self.get_content(one);
-        let tile_two = self.get_content(two);
-        self.switch_in_slots(one, tile_one, two, tile_two);
-
-        has_patterns
-    }
-
-    fn find_patterns_in_columns(&self) -> Vec<Pattern> {
-        let mut patterns = vec![];
-        let mut count = 0;
-        let mut current = None;
-        for (column_index, column) in self.slots.iter().enumerate() {
-            for (row, content) in column.iter().enumerate() {
-                if let Some(animal) = current.take() {
-                    if animal == content.collectable {
-                        current = Some(animal);
-                        count += 1;
-                    } else {
-                        current = Some(content.collectable.clone());
-                        count = 1;
-                    }
-                } else {
-                    current = Some(content.collectable.clone());
-                    count = 1;
+        for event in events {
+            match event {
+                BoardEvent::Switched { one, two } => {
+                    let tile_one = self.entity(&one);
+                    let tile_two = self.entity(&two);
+                    commands
+                        .entity(tile_one)
+                        .insert(vec![Move::move_to(self.geometry.position(&two))])
+                        .insert(two.clone());
+                    commands
+                        .entity(tile_two)
+                        .insert(vec![Move::move_to(self.geometry.position(&one))])
+                        .insert(one.clone());
+                    self.entities.insert(one, tile_two);
+                    self.entities.insert(two, tile_one);
+                }
+                BoardEvent::Matched { .. } | BoardEvent::Activated { .. } => {}
+                BoardEvent::SpecialCreated { slot, special } => {
+                    commands
+                        .entity(self.entity(&slot))
+                        .insert(TextureAtlasSprite {
+                            color: special_color(&special),
+                            ..TextureAtlasSprite::default()
+                        });
                 }
-                if count >= 3 {
-                    patterns.push(Pattern::Line {
-                        slots: vec![
-                            Slot {
-                                row,
-                                column: column_index,
-                            },
-                            Slot {
-                                row: row - 1,
-                                column: column_index,
-                            },
-                            Slot {
-                                row: row - 2,
-                                column: column_index,
-                            },
-                        ],
-                    })
+                BoardEvent::Removed { slot, collectable } => {
+                    let entity = self.entities.remove(&slot).unwrap();
+                    commands
+                        .entity(entity)
+                        .remove::<Slot>()
+                        .insert(collectable.get_animation())
+                        .insert(vec![Move::process(self.grid.cascade().max(1))]);
                 }
-            }
-            current = None;
-            count = 0;
-        }
-
-        patterns
-    }
-
-    fn find_patterns_in_rows(&self) -> Vec<Pattern> {
-        let mut patterns = vec![];
-        let mut count = 0;
-        let mut current = None;
-        for row in 0..self.slots.first().unwrap().len() {
-            for column_index in 0..self.slots.len() {
-                let content = self.slots.get(column_index).unwrap().get(row).unwrap();
-                if let Some(animal) = current.take() {
-                    if animal == content.collectable {
-                        current = Some(animal);
-                        count += 1;
-                    } else {
-                        current = Some(content.collectable.clone());
-                        count = 1;
+                BoardEvent::Dropped { from, to } => {
+                    let entity = self.entities.remove(&from).unwrap();
+                    commands
+                        .entity(entity)
+                        .insert(vec![Move::move_to(self.geometry.position(&to))])
+                        .insert(to.clone());
+                    self.entities.insert(to, entity);
+                }
+                BoardEvent::Spawned {
+                    slot,
+                    collectable,
+                    drop_height,
+                } => {
+                    let entity = spawn_collectable(
+                        commands,
+                        slot.clone(),
+                        collectable,
+                        &self.geometry,
+                        drop_height as f32 * TILE_SIZE,
+                        textures,
+                    );
+                    self.entities.insert(slot, entity);
+                }
+                BoardEvent::Shuffled { moves } => {
+                    // chained tiles are not part of the shuffle and keep their entities
+                    let mut entities = self.entities.clone();
+                    for (from, to) in moves {
+                        let entity = self.entity(&from);
+                        commands
+                            .entity(entity)
+                            .insert(vec![Move::move_to(self.geometry.position(&to))])
+                            .insert(to.clone());
+                        entities.insert(to, entity);
                     }
-                } else {
-                    current = Some(content.collectable.clone());
-                    count = 1;
+                    self.entities = entities;
                 }
-                if count >= 3 {
-                    patterns.push(Pattern::Line {
-                        slots: vec![
-                            Slot {
-                                column: column_index,
-                                row,
-                            },
-                            Slot {
-                                column: column_index - 1,
-                                row,
-                            },
-                            Slot {
-                                column: column_index - 2,
-                                row,
-                            },
-                        ],
-                    })
+                BoardEvent::ObstacleCleared { slot, .. } => {
+                    if let Some(entity) = self.obstacles.remove(&slot) {
+                        commands.entity(entity).despawn();
+                    }
                 }
             }
-            current = None;
-            count = 0;
         }
-
-        patterns
-    }
-
-    fn neighbors(&self, position: &Slot) -> Vec<Slot> {
-        let mut neighbors = vec![];
-        match position {
-            Slot { row: 0, column: 0 } => {
-                neighbors.push(position.walk(1, 0));
-                neighbors.push(position.walk(0, 1));
-            }
-            Slot {
-                row: 0,
-                column: width,
-            } if width == &(self.width - 1) => {
-                neighbors.push(position.walk(1, 0));
-                neighbors.push(position.walk(0, -1));
-            }
-            Slot {
-                row: height,
-                column: width,
-            } if width == &(self.width - 1) && height == &(self.height - 1) => {
-                neighbors.push(position.walk(-1, 0));
-                neighbors.push(position.walk(0, -1));
-            }
-            Slot {
-                row: height,
-                column: 0,
-            } if height == &(self.height - 1) => {
-                neighbors.push(position.walk(-1, 0));
-                neighbors.push(position.walk(0, 1));
-            }
-            Slot { row: 0, column: _ } => {
-                neighbors.push(position.walk(0, 1));
-                neighbors.push(position.walk(0, -1));
-                neighbors.push(position.walk(1, 0));
-            }
-            Slot { row: _, column: 0 } => {
-                neighbors.push(position.walk(1, 0));
-                neighbors.push(position.walk(0, 1));
-                neighbors.push(position.walk(-1, 0));
-            }
-            Slot {
-                row: height,
-                column: _,
-            } if height == &(self.height - 1) => {
-                neighbors.push(position.walk(0, 1));
-                neighbors.push(position.walk(-1, 0));
-                neighbors.push(position.walk(0, -1));
-            }
-            Slot {
-                row: _,
-                column: width,
-            } if width == &(self.width - 1) => {
-                neighbors.push(position.walk(-1, 0));
-                neighbors.push(position.walk(0, -1));
-                neighbors.push(position.walk(1, 0));
-            }
-            Slot {
-                row: height,
-                column: width,
-            } if height >= &0 && height < &self.height && width >= &0 && width < &self.width => {
-                neighbors.push(position.walk(1, 0));
-                neighbors.push(position.walk(0, 1));
-                neighbors.push(position.walk(-1, 0));
-                neighbors.push(position.walk(0, -1));
-            }
-            slot => panic!("The slot {:?} is outside of the board", slot),
-        };
-        neighbors
     }
 }
 
-fn drop_random_collectable(
+fn spawn_collectable(
     commands: &mut Commands,
-    goal: Vec2,
-    drop_height: f32,
     slot: Slot,
+    collectable: Collectable,
+    geometry: &BoardGeometry,
+    drop_height: f32,
     textures: &TextureAssets,
-) -> SlotContent {
-    let collectable: Collectable = random();
-    let entity = commands
+) -> Entity {
+    let goal = geometry.position(&slot);
+    commands
         .spawn_bundle(SpriteSheetBundle {
             texture_atlas: collectable.get_texture(textures),
             transform: Transform::from_translation(Vec3::new(goal.x, goal.y + drop_height, 5.)),
             ..SpriteSheetBundle::default()
         })
-        .insert(vec![Move::move_to_slot(&slot)])
+        .insert(vec![Move::move_to(goal)])
         .insert(slot)
-        .insert(collectable.clone())
-        .id();
-    SlotContent {
-        entity,
-        collectable,
-    }
+        .insert(collectable)
+        .id()
 }
 
 fn reset(
     mut commands: Commands,
     collectables: Query<Entity, With<Collectable>>,
+    obstacles: Query<Entity, With<Obstacle>>,
     scroll: Query<Entity, With<Scroll>>,
 ) {
     for entity in collectables.iter() {
         commands.entity(entity).despawn();
     }
-    for entity in scroll.iter() {
+    for entity in obstacles.iter() {
         commands.entity(entity).despawn();
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use crate::board::Board;
-    use crate::matcher::{Collectable, Pattern, Slot, SlotContent};
-    use bevy::prelude::*;
-
-    #[test]
-    fn finds_lines_in_rows_on_small_board() {
-        let mut board = Board {
-            height: 3,
-            width: 3,
-            animating: false,
-            slots: vec![
-                vec![
-                    SlotContent {
-                        entity: Entity::new(0),
-                        collectable: Collectable::Jar
-                    };
-                    3
-                ];
-                3
-            ],
-        };
-        board
-            .slots
-            .get_mut(1)
-            .unwrap()
-            .get_mut(1)
-            .unwrap()
-            .collectable = Collectable::Red;
-
-        assert_eq!(
-            board.find_patterns_in_rows(),
-            vec![
-                Pattern::Line {
-                    slots: vec![Slot::new(0, 2), Slot::new(0, 1), Slot::new(0, 0),]
-                },
-                Pattern::Line {
-                    slots: vec![Slot::new(2, 2), Slot::new(2, 1), Slot::new(2, 0),]
-                }
-            ]
-        )
-    }
-
-    #[test]
-    fn finds_lines_in_rows() {
-        let size = 5;
-        let mut board = Board {
-            height: size,
-            width: size,
-            animating: false,
-            slots: vec![
-                vec![
-                    SlotContent {
-                        entity: Entity::new(0),
-                        collectable: Collectable::Jar
-                    };
-                    size
-                ];
-                size
-            ],
-        };
-        for index in 0..size {
-            board
-                .slots
-                .get_mut(index)
-                .unwrap()
-                .get_mut(index)
-                .unwrap()
-                .collectable = Collectable::Red;
-        }
-
-        assert_eq!(
-            board.find_patterns_in_rows(),
-            vec![
-                Pattern::Line {
-                    slots: vec![Slot::new(0, 3), Slot::new(0, 2), Slot::new(0, 1),]
-                },
-                Pattern::Line {
-                    slots: vec![Slot::new(0, 4), Slot::new(0, 3), Slot::new(0, 2),]
-                },
-                Pattern::Line {
-                    slots: vec![Slot::new(1, 4), Slot::new(1, 3), Slot::new(1, 2),]
-                },
-                Pattern::Line {
-                    slots: vec![Slot::new(3, 2), Slot::new(3, 1), Slot::new(3, 0),]
-                },
-                Pattern::Line {
-                    slots: vec![Slot::new(4, 2), Slot::new(4, 1), Slot::new(4, 0),]
-                },
-                Pattern::Line {
-                    slots: vec![Slot::new(4, 3), Slot::new(4, 2), Slot::new(4, 1),]
-                }
-            ]
-        )
-    }
-
-    #[test]
-    fn correctly_gives_neighbors() {
-        let board = Board {
-            height: 5,
-            width: 5,
-            animating: false,
-            slots: vec![
-                vec![
-                    SlotContent {
-                        entity: Entity::new(0),
-                        collectable: Collectable::Jar
-                    };
-                    5
-                ];
-                5
-            ],
-        };
-        assert_eq!(
-            board.neighbors(&Slot::new(0, 0)),
-            vec![Slot::new(1, 0), Slot::new(0, 1)]
-        );
-        assert_eq!(
-            board.neighbors(&Slot::new(0, 3)),
-            vec![Slot::new(0, 4), Slot::new(0, 2), Slot::new(1, 3)]
-        );
-        assert_eq!(
-            board.neighbors(&Slot::new(2, 3)),
-            vec![
-                Slot::new(3, 3),
-                Slot::new(2, 4),
-                Slot::new(1, 3),
-                Slot::new(2, 2)
-            ]
-        );
+    for entity in scroll.iter() {
+        commands.entity(entity).despawn();
     }
 }
@@ -0,0 +1,241 @@
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::render::camera::Camera;
+use bevy::ui::UiSystem;
+
+/// Size of the visible game world. The window shows all of it, no matter its own size.
+pub const WORLD_WIDTH: f32 = 800.;
+pub const WORLD_HEIGHT: f32 = 600.;
+
+pub struct CameraPlugin;
+
+/// This plugin adds the camera of the game world and scales it with the window
+/// The whole world stays visible. If the window has another aspect ratio, the remaining space is
+/// filled with the clear color on both sides (letterboxing).
+/// The menus and overlays of all states share one UI camera, so stacked states like a paused run
+/// don't draw their UI twice. Their layout is made for a window of the world's size and is
+/// scaled and letterboxed together with the world.
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<UiScale>()
+            .add_system_set(
+                SystemSet::on_exit(GameState::Loading)
+                    .with_system(set_camera.system())
+                    .with_system(set_ui_camera.system()),
+            )
+            .add_system(fit_camera.system())
+            // new nodes are scaled before their first layout
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                fit_ui.system().before(UiSystem::Flex),
+            );
+    }
+}
+
+/// How the UI, laid out for a window of the world's size, fits into the actual window
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UiScale {
+    /// Window pixels per pixel of the layout
+    pub factor: f32,
+    /// Space between the window border and the letterboxed world
+    pub offset: Vec2,
+}
+
+impl Default for UiScale {
+    fn default() -> Self {
+        UiScale {
+            factor: 1.,
+            offset: Vec2::ZERO,
+        }
+    }
+}
+
+/// The style and font sizes that a UI node was spawned with
+struct DesignLayout {
+    style: Style,
+    font_sizes: Vec<f32>,
+}
+
+impl DesignLayout {
+    fn new(style: &Style, text: Option<&Text>) -> Self {
+        DesignLayout {
+            style: style.clone(),
+            font_sizes: text
+                .map(|text| {
+                    text.sections
+                        .iter()
+                        .map(|section| section.style.font_size)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Scale all pixel values of the layout. Other values can be changed by the game and stay.
+    ///
+    /// Nodes at the root of the UI are placed relative to the letterboxed world.
+    fn apply(&self, style: &mut Style, text: Option<&mut Text>, is_root: bool, scale: &UiScale) {
+        let factor = scale.factor;
+        let offset = if is_root && self.style.position_type == PositionType::Absolute {
+            scale.offset
+        } else {
+            Vec2::ZERO
+        };
+        let design = &self.style;
+        scale_val(
+            &mut style.position.left,
+            design.position.left,
+            factor,
+            offset.x,
+        );
+        scale_val(
+            &mut style.position.right,
+            design.position.right,
+            factor,
+            offset.x,
+        );
+        scale_val(
+            &mut style.position.top,
+            design.position.top,
+            factor,
+            offset.y,
+        );
+        scale_val(
+            &mut style.position.bottom,
+            design.position.bottom,
+            factor,
+            offset.y,
+        );
+        for (value, design) in [
+            (&mut style.size, design.size),
+            (&mut style.min_size, design.min_size),
+            (&mut style.max_size, design.max_size),
+        ]
+        .iter_mut()
+        {
+            scale_val(&mut value.width, design.width, factor, 0.);
+            scale_val(&mut value.height, design.height, factor, 0.);
+        }
+        for (value, design) in [
+            (&mut style.margin, design.margin),
+            (&mut style.padding, design.padding),
+            (&mut style.border, design.border),
+        ]
+        .iter_mut()
+        {
+            scale_val(&mut value.left, design.left, factor, 0.);
+            scale_val(&mut value.right, design.right, factor, 0.);
+            scale_val(&mut value.top, design.top, factor, 0.);
+            scale_val(&mut value.bottom, design.bottom, factor, 0.);
+        }
+        if let Some(text) = text {
+            for (section, font_size) in text.sections.iter_mut().zip(self.font_sizes.iter()) {
+                section.style.font_size = font_size * factor;
+            }
+        }
+    }
+}
+
+fn scale_val(value: &mut Val, design: Val, factor: f32, offset: f32) {
+    if let Val::Px(pixels) = design {
+        *value = Val::Px(pixels * factor + offset);
+    }
+}
+
+/// The camera looking at the game world, as opposed to the UI camera of the menus
+pub struct MainCamera;
+
+fn set_camera(mut commands: Commands) {
+    commands
+        .spawn_bundle(OrthographicCameraBundle {
+            transform: Transform::from_translation(Vec3::new(
+                WORLD_WIDTH / 2.,
+                WORLD_HEIGHT / 2.,
+                999.9,
+            )),
+            ..OrthographicCameraBundle::new_2d()
+        })
+        .insert(MainCamera);
+}
+
+fn set_ui_camera(mut commands: Commands) {
+    commands.spawn_bundle(UiCameraBundle::default());
+}
+
+fn fit_camera(
+    windows: Res<Windows>,
+    mut cameras: Query<&mut OrthographicProjection, With<MainCamera>>,
+    mut ui_scale: ResMut<UiScale>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    // a minimized window has no size
+    if window.width() <= 0. || window.height() <= 0. {
+        return;
+    }
+    let scale = (WORLD_WIDTH / window.width()).max(WORLD_HEIGHT / window.height());
+    for mut projection in cameras.iter_mut() {
+        // setting the same scale would still mark the projection as changed
+        if (projection.scale - scale).abs() > f32::EPSILON {
+            projection.scale = scale;
+        }
+    }
+    let factor = 1. / scale;
+    let fitted = UiScale {
+        factor,
+        offset: (Vec2::new(window.width(), window.height())
+            - Vec2::new(WORLD_WIDTH, WORLD_HEIGHT) * factor)
+            / 2.,
+    };
+    // only touch the scale if it differs, so the UI is not scaled again every frame
+    if *ui_scale != fitted {
+        *ui_scale = fitted;
+    }
+}
+
+/// Scale new UI nodes, and all of them whenever the window size changes
+fn fit_ui(
+    mut commands: Commands,
+    ui_scale: Res<UiScale>,
+    mut nodes: QuerySet<(
+        Query<
+            (Entity, &mut Style, Option<&mut Text>, Option<&Parent>),
+            (With<Node>, Without<DesignLayout>),
+        >,
+        Query<(
+            &DesignLayout,
+            &mut Style,
+            Option<&mut Text>,
+            Option<&Parent>,
+        )>,
+    )>,
+) {
+    for (entity, mut style, mut text, parent) in nodes.q0_mut().iter_mut() {
+        let design = DesignLayout::new(&style, text.as_deref());
+        design.apply(&mut style, text.as_deref_mut(), parent.is_none(), &ui_scale);
+        commands.entity(entity).insert(design);
+    }
+    if ui_scale.is_changed() {
+        for (design, mut style, mut text, parent) in nodes.q1_mut().iter_mut() {
+            design.apply(&mut style, text.as_deref_mut(), parent.is_none(), &ui_scale);
+        }
+    }
+}
+
+/// Convert a position in the window, like the cursor position, to world coordinates
+pub fn screen_to_world(
+    position: Vec2,
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Vec2 {
+    let window_size = Vec2::new(window.width(), window.height());
+    let normalized = position / window_size * 2. - Vec2::splat(1.);
+    let screen_to_world = camera_transform.compute_matrix() * camera.projection_matrix.inverse();
+
+    screen_to_world
+        .transform_point3(normalized.extend(0.))
+        .truncate()
+}
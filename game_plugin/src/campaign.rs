@@ -0,0 +1,379 @@
+use crate::board::{
+    Board, Cauldron, Score, MAX_BOARD_HEIGHT, MAX_BOARD_WIDTH, MAX_ORDERS, MIN_ORDERS,
+};
+use crate::loading::{CampaignAssets, FontAssets};
+use crate::lost::{EndRun, RunOutcome};
+use crate::matcher::{Collectable, SpawnWeights};
+use crate::menu::ButtonMaterials;
+use crate::mode::{Limit, RunSettings};
+use crate::save::SaveData;
+use crate::simulation::Layout;
+use crate::{GameState, SystemLabels};
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use serde::{Deserialize, Serialize};
+
+pub struct CampaignPlugin;
+
+/// This plugin adds the campaign. Its levels are defined in `assets/data/campaign.levels`.
+/// Levels are picked in the level select screen during `GameState::LevelSelect`.
+/// The stars earned per level are kept in the [SaveData] and unlock the next level.
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<Campaign>()
+            .init_asset_loader::<CampaignLoader>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::LevelSelect)
+                    .with_system(setup_level_select.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::LevelSelect)
+                    .with_system(click_level_button.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::LevelSelect)
+                    .with_system(remove_level_select.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(check_level_goal.system().after(SystemLabels::Animate)),
+            );
+    }
+}
+
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "0e4a4f4e-6b8d-4b1c-a3f2-2f6c1a9d7e31"]
+pub struct Campaign {
+    pub levels: Vec<Level>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Level {
+    pub name: String,
+    pub layout: Layout,
+    pub collectables: Vec<Collectable>,
+    /// How often the collectables show up. Missing ones have a weight of one.
+    #[serde(default)]
+    pub weights: SpawnWeights,
+    /// Potions to brew to complete the level
+    pub potions: usize,
+    pub limit: Limit,
+    pub stars: StarThresholds,
+    /// Coins to pay for shuffling the board when no switch is left
+    #[serde(default)]
+    pub shuffle_cost: usize,
+    /// Number of customer orders that are open at the same time
+    #[serde(default = "default_orders")]
+    pub orders: usize,
+}
+
+fn default_orders() -> usize {
+    MIN_ORDERS
+}
+
+/// Coins needed for the second and third star. Completing the level earns the first star.
+#[derive(Clone, Debug, Deserialize)]
+pub struct StarThresholds {
+    pub two: usize,
+    pub three: usize,
+}
+
+impl Level {
+    pub fn settings(&self, index: usize) -> RunSettings {
+        RunSettings {
+            name: self.name.clone(),
+            layout: self.layout.clone(),
+            collectables: self.collectables.clone(),
+            weights: self.weights.clone(),
+            limit: Some(self.limit),
+            shuffle_cost: Some(self.shuffle_cost),
+            potions: Some(self.potions),
+            orders: self.orders,
+            mode: None,
+            level: Some(index),
+            undo: false,
+        }
+    }
+
+    pub fn stars(&self, coins: usize) -> usize {
+        if coins >= self.stars.three {
+            3
+        } else if coins >= self.stars.two {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// Can the level be played on the screen and completed?
+    fn validate(&self) -> Result<(), String> {
+        if self.collectables.is_empty() {
+            return Err(format!("Level '{}' has no collectables", self.name));
+        }
+        if self.layout.width() > MAX_BOARD_WIDTH || self.layout.height() > MAX_BOARD_HEIGHT {
+            return Err(format!(
+                "The board of level '{}' is larger than {}x{}",
+                self.name, MAX_BOARD_WIDTH, MAX_BOARD_HEIGHT
+            ));
+        }
+        if self.potions == 0 {
+            return Err(format!("Level '{}' needs no potions", self.name));
+        }
+        if !(MIN_ORDERS..=MAX_ORDERS).contains(&self.orders) {
+            return Err(format!(
+                "Level '{}' needs between {} and {} orders",
+                self.name, MIN_ORDERS, MAX_ORDERS
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// The best number of stars earned in each level
+#[derive(Default, Deserialize, Serialize)]
+pub struct Progress {
+    stars: Vec<usize>,
+}
+
+impl Progress {
+    pub fn stars(&self, level: usize) -> usize {
+        self.stars.get(level).copied().unwrap_or(0)
+    }
+
+    /// No level was completed yet
+    pub fn is_empty(&self) -> bool {
+        self.stars.iter().all(|&stars| stars == 0)
+    }
+
+    /// A level is unlocked once the level before it was completed
+    pub fn is_unlocked(&self, level: usize) -> bool {
+        level == 0 || self.stars(level - 1) > 0
+    }
+
+    /// Returns true if this is the best result for the level so far
+    pub fn complete(&mut self, level: usize, stars: usize) -> bool {
+        if self.stars(level) >= stars {
+            return false;
+        }
+        if self.stars.len() <= level {
+            self.stars.resize(level + 1, 0);
+        }
+        self.stars[level] = stars;
+        true
+    }
+}
+
+struct LevelSelect;
+
+struct LevelButton(usize);
+
+struct BackButton;
+
+fn setup_level_select(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_materials: Res<ButtonMaterials>,
+    campaign_assets: Res<CampaignAssets>,
+    campaigns: Res<Assets<Campaign>>,
+    save: Res<SaveData>,
+) {
+    let levels = campaigns
+        .get(&campaign_assets.campaign)
+        .map(|campaign| campaign.levels.as_slice())
+        .unwrap_or_default();
+    for (index, level) in levels.iter().enumerate() {
+        let unlocked = save.campaign.is_unlocked(index);
+        let label = if unlocked {
+            format!(
+                "{}. {} ({}/3)",
+                index + 1,
+                level.name,
+                save.campaign.stars(index)
+            )
+        } else {
+            format!("{}. Locked", index + 1)
+        };
+        let button = spawn_button(
+            &mut commands,
+            label,
+            40. + index as f32 * 58.,
+            if unlocked {
+                button_materials.normal.clone()
+            } else {
+                button_materials.locked.clone()
+            },
+            &font_assets,
+        );
+        if unlocked {
+            commands.entity(button).insert(LevelButton(index));
+        }
+    }
+    let back = spawn_button(
+        &mut commands,
+        "Back".to_owned(),
+        40. + levels.len() as f32 * 58. + 20.,
+        button_materials.normal.clone(),
+        &font_assets,
+    );
+    commands.entity(back).insert(BackButton);
+}
+
+fn spawn_button(
+    commands: &mut Commands,
+    label: String,
+    top: f32,
+    material: Handle<ColorMaterial>,
+    font_assets: &FontAssets,
+) -> Entity {
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(260.0), Val::Px(48.0)),
+                margin: Rect::all(Val::Auto),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(20.0),
+                    top: Val::Px(top),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material,
+            ..Default::default()
+        })
+        .insert(LevelSelect)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: label,
+                            style: TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 22.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(LevelSelect);
+        })
+        .id()
+}
+
+fn click_level_button(
+    button_materials: Res<ButtonMaterials>,
+    mut state: ResMut<State<GameState>>,
+    mut settings: ResMut<RunSettings>,
+    campaign_assets: Res<CampaignAssets>,
+    campaigns: Res<Assets<Campaign>>,
+    mut interaction_query: Query<
+        (
+            &Interaction,
+            &mut Handle<ColorMaterial>,
+            Option<&LevelButton>,
+            Option<&BackButton>,
+        ),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut material, level_button, back_button) in interaction_query.iter_mut() {
+        if level_button.is_none() && back_button.is_none() {
+            // locked levels can not be clicked
+            continue;
+        }
+        match *interaction {
+            Interaction::Clicked => {
+                if let Some(LevelButton(index)) = level_button {
+                    let level = campaigns
+                        .get(&campaign_assets.campaign)
+                        .and_then(|campaign| campaign.levels.get(*index));
+                    if let Some(level) = level {
+                        *settings = level.settings(*index);
+                        state.set(GameState::Playing).unwrap();
+                    }
+                } else {
+                    state.set(GameState::Menu).unwrap();
+                }
+                return;
+            }
+            Interaction::Hovered => {
+                *material = button_materials.hovered.clone();
+            }
+            Interaction::None => {
+                *material = button_materials.normal.clone();
+            }
+        }
+    }
+}
+
+fn remove_level_select(mut commands: Commands, elements: Query<Entity, With<LevelSelect>>) {
+    for entity in elements.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Complete the level once enough potions are brewed and the board came to rest
+fn check_level_goal(
+    settings: Res<RunSettings>,
+    cauldron: Res<Cauldron>,
+    score: Res<Score>,
+    board: Res<Board>,
+    campaign_assets: Res<CampaignAssets>,
+    campaigns: Res<Assets<Campaign>>,
+    mut save: ResMut<SaveData>,
+    mut end_run: EventWriter<EndRun>,
+) {
+    let index = match settings.level {
+        Some(index) => index,
+        None => return,
+    };
+    let level = match campaigns
+        .get(&campaign_assets.campaign)
+        .and_then(|campaign| campaign.levels.get(index))
+    {
+        Some(level) => level,
+        None => return,
+    };
+    if cauldron.finished_recipes < level.potions || board.animating || !board.grid.is_settled() {
+        return;
+    }
+    let stars = level.stars(score.money);
+    if save.campaign.complete(index, stars) {
+        save.store();
+    }
+    end_run.send(EndRun {
+        outcome: RunOutcome::LevelCompleted { stars },
+    });
+}
+
+#[derive(Default)]
+struct CampaignLoader;
+
+impl AssetLoader for CampaignLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let campaign = ron::de::from_bytes::<Campaign>(bytes)?;
+            for level in campaign.levels.iter() {
+                level.validate().map_err(anyhow::Error::msg)?;
+            }
+            load_context.set_default_asset(LoadedAsset::new(campaign));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["levels"]
+    }
+}
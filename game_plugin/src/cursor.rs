@@ -0,0 +1,179 @@
+use crate::board::{Board, PickSlot, Selected, TILE_SIZE};
+use crate::matcher::Slot;
+use crate::mode::RunSettings;
+use crate::replay::Replays;
+use crate::{GameState, SystemLabels};
+use bevy::prelude::*;
+
+pub struct CursorPlugin;
+
+/// This plugin lets the player use the keyboard or a gamepad instead of the mouse
+/// Arrow keys, WASD or the d-pad move a cursor over the board. Space, Enter or the south button
+/// select the tile below the cursor. A direction then switches the selected tile with its neighbor.
+impl Plugin for CursorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<CursorMaterial>()
+            .insert_resource(BoardCursor {
+                slot: Slot::new(0, 0),
+                visible: false,
+            })
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(spawn_cursor.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(move_cursor.system().label(SystemLabels::ReadInput))
+                    .with_system(show_cursor.system().after(SystemLabels::ReadInput)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing).with_system(remove_cursor.system()),
+            );
+    }
+}
+
+struct BoardCursor {
+    slot: Slot,
+    /// The cursor is hidden until the keyboard or a gamepad is used and while the mouse is used
+    visible: bool,
+}
+
+struct CursorMaterial(Handle<ColorMaterial>);
+
+impl FromWorld for CursorMaterial {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        CursorMaterial(materials.add(Color::rgba(1., 1., 1., 0.35).into()))
+    }
+}
+
+struct CursorSprite;
+
+fn spawn_cursor(
+    mut commands: Commands,
+    material: Res<CursorMaterial>,
+    settings: Res<RunSettings>,
+    mut cursor: ResMut<BoardCursor>,
+) {
+    *cursor = BoardCursor {
+        slot: Slot::new(settings.layout.height() / 2, settings.layout.width() / 2),
+        visible: false,
+    };
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite::new(Vec2::new(TILE_SIZE, TILE_SIZE)),
+            material: material.0.clone(),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..SpriteBundle::default()
+        })
+        .insert(CursorSprite);
+}
+
+fn move_cursor(
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    board: Res<Board>,
+    selection: Res<Selected>,
+    replays: Res<Replays>,
+    mut cursor: ResMut<BoardCursor>,
+    mut picks: EventWriter<PickSlot>,
+) {
+    if replays.is_playing() {
+        return;
+    }
+    if mouse_buttons.just_pressed(MouseButton::Left) {
+        if cursor.visible {
+            cursor.visible = false;
+        }
+        return;
+    }
+    let pressed = |key_codes: &[KeyCode], button: GamepadButtonType| {
+        key_codes
+            .iter()
+            .any(|key_code| keys.just_pressed(*key_code))
+            || gamepads
+                .iter()
+                .any(|gamepad| gamepad_buttons.just_pressed(GamepadButton(*gamepad, button)))
+    };
+    let direction = if pressed(&[KeyCode::Up, KeyCode::W], GamepadButtonType::DPadUp) {
+        Some((1, 0))
+    } else if pressed(&[KeyCode::Down, KeyCode::S], GamepadButtonType::DPadDown) {
+        Some((-1, 0))
+    } else if pressed(&[KeyCode::Left, KeyCode::A], GamepadButtonType::DPadLeft) {
+        Some((0, -1))
+    } else if pressed(&[KeyCode::Right, KeyCode::D], GamepadButtonType::DPadRight) {
+        Some((0, 1))
+    } else {
+        None
+    };
+    let confirm = pressed(&[KeyCode::Space, KeyCode::Return], GamepadButtonType::South);
+    if direction.is_none() && !confirm {
+        return;
+    }
+    if !cursor.visible {
+        // the first press only shows where the cursor is
+        cursor.visible = true;
+        return;
+    }
+
+    if confirm {
+        picks.send(PickSlot {
+            slot: cursor.slot.clone(),
+        });
+    } else if let Some((row_delta, column_delta)) = direction {
+        if *selection == Some(cursor.slot.clone()) {
+            if let Some(neighbor) = step(&board, &cursor.slot, row_delta, column_delta) {
+                picks.send(PickSlot { slot: neighbor });
+            }
+            return;
+        }
+        // skip holes and stones
+        let mut next = step(&board, &cursor.slot, row_delta, column_delta);
+        while let Some(slot) = next {
+            if board.grid.has_tile(&slot) {
+                cursor.slot = slot;
+                return;
+            }
+            next = step(&board, &slot, row_delta, column_delta);
+        }
+    }
+}
+
+/// The neighboring slot in the given direction, if it is part of the board
+fn step(board: &Board, slot: &Slot, row_delta: i64, column_delta: i64) -> Option<Slot> {
+    let row = slot.row as i64 + row_delta;
+    let column = slot.column as i64 + column_delta;
+    if row < 0
+        || column < 0
+        || row >= board.grid.height() as i64
+        || column >= board.grid.width() as i64
+    {
+        return None;
+    }
+
+    Some(Slot::new(row as usize, column as usize))
+}
+
+fn show_cursor(
+    cursor: Res<BoardCursor>,
+    board: Res<Board>,
+    mut sprites: Query<(&mut Transform, &mut Visible), With<CursorSprite>>,
+) {
+    if !cursor.is_changed() {
+        return;
+    }
+    for (mut transform, mut visible) in sprites.iter_mut() {
+        transform.translation = board.geometry.position(&cursor.slot).extend(7.);
+        visible.is_visible = cursor.visible;
+    }
+}
+
+fn remove_cursor(mut commands: Commands, sprites: Query<Entity, With<CursorSprite>>) {
+    for entity in sprites.iter() {
+        commands.entity(entity).despawn();
+    }
+}
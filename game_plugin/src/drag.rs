@@ -0,0 +1,229 @@
+use crate::animate::Move;
+use crate::board::{Board, HammerArmed, PickSlot, Selected, TILE_SIZE};
+use crate::camera::{screen_to_world, MainCamera};
+use crate::matcher::Slot;
+use crate::mode::RunLimit;
+use crate::replay::Replays;
+use crate::{GameState, SystemLabels};
+use bevy::input::touch::Touches;
+use bevy::prelude::*;
+use bevy::render::camera::Camera;
+use std::ops::Deref;
+
+/// Dragging a tile this far towards a neighbor switches both
+const SWITCH_DISTANCE: f32 = TILE_SIZE / 2.;
+/// Releasing a tile that was moved less than this counts as a click
+const CLICK_DISTANCE: f32 = TILE_SIZE / 4.;
+
+pub struct DragPlugin;
+
+/// This plugin reads the mouse and touch input on the board
+/// Pressing a tile picks it, so two clicks or taps switch neighbors. Tiles can also be dragged
+/// towards a neighbor. They follow the pointer and snap back if the switch is not possible.
+impl Plugin for DragPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource::<Dragged>(None)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(reset_drag.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(drag_tile.system().label(SystemLabels::ReadInput)),
+            );
+    }
+}
+
+type Dragged = Option<Drag>;
+
+struct Drag {
+    slot: Slot,
+    entity: Entity,
+    /// Where the pointer pressed the tile
+    start: Vec2,
+    /// Pressing the selected tile deselects it, but only once it was released without dragging
+    pick_on_release: bool,
+}
+
+/// The mouse or the first finger on the screen
+struct Pointer {
+    /// In world coordinates
+    position: Vec2,
+    just_pressed: bool,
+    just_released: bool,
+}
+
+fn pointer(
+    windows: &Windows,
+    camera: (&Camera, &GlobalTransform),
+    mouse_buttons: &Input<MouseButton>,
+    touches: &Touches,
+) -> Option<Pointer> {
+    let window = windows.get_primary().expect("No primary window found");
+    let (camera, camera_transform) = camera;
+    if let Some(touch) = touches
+        .iter()
+        .next()
+        .or_else(|| touches.iter_just_released().next())
+    {
+        return Some(Pointer {
+            position: screen_to_world(touch.position(), window, camera, camera_transform),
+            just_pressed: touches.just_pressed(touch.id()),
+            just_released: touches.just_released(touch.id()),
+        });
+    }
+    window.cursor_position().map(|position| Pointer {
+        position: screen_to_world(position, window, camera, camera_transform),
+        just_pressed: mouse_buttons.just_pressed(MouseButton::Left),
+        just_released: mouse_buttons.just_released(MouseButton::Left),
+    })
+}
+
+fn reset_drag(mut dragged: ResMut<Dragged>) {
+    *dragged = None;
+}
+
+fn drag_tile(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    board: Res<Board>,
+    selection: Res<Selected>,
+    limit: Res<RunLimit>,
+    hammer: Res<HammerArmed>,
+    replays: Res<Replays>,
+    mut dragged: ResMut<Dragged>,
+    cameras: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut transforms: Query<&mut Transform>,
+    mut picks: EventWriter<PickSlot>,
+) {
+    if replays.is_playing() {
+        return;
+    }
+    let camera = match cameras.single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let pointer = match pointer(&windows, camera, &mouse_buttons, &touches) {
+        Some(pointer) => pointer,
+        None => {
+            // the pointer left the window
+            if let Some(drag) = dragged.take() {
+                snap_back(&mut commands, &board, &drag);
+            }
+            return;
+        }
+    };
+    if pointer.just_pressed {
+        if let Some(drag) = dragged.take() {
+            snap_back(&mut commands, &board, &drag);
+        }
+        *dragged = press_tile(
+            &pointer,
+            &board,
+            selection.deref(),
+            &limit,
+            &hammer,
+            &mut picks,
+        );
+        return;
+    }
+    let drag = match dragged.take() {
+        Some(drag) => drag,
+        None => return,
+    };
+
+    // follow the pointer along the axis it moved the most, but at most one slot far
+    let offset = pointer.position - drag.start;
+    let (row_delta, column_delta, along) = if offset.x.abs() > offset.y.abs() {
+        (
+            0,
+            offset.x.signum() as i64,
+            Vec2::new(offset.x.clamp(-TILE_SIZE, TILE_SIZE), 0.),
+        )
+    } else {
+        (
+            offset.y.signum() as i64,
+            0,
+            Vec2::new(0., offset.y.clamp(-TILE_SIZE, TILE_SIZE)),
+        )
+    };
+    if along.length() >= SWITCH_DISTANCE {
+        let neighbor = board
+            .grid
+            .neighbors(&drag.slot)
+            .into_iter()
+            .find(|neighbor| {
+                neighbor.row as i64 - drag.slot.row as i64 == row_delta
+                    && neighbor.column as i64 - drag.slot.column as i64 == column_delta
+            });
+        match neighbor {
+            // the switch snaps the tile back by itself if it is not possible
+            Some(neighbor) => picks.send(PickSlot { slot: neighbor }),
+            None => snap_back(&mut commands, &board, &drag),
+        }
+        return;
+    }
+    if pointer.just_released {
+        snap_back(&mut commands, &board, &drag);
+        if drag.pick_on_release && offset.length() < CLICK_DISTANCE {
+            picks.send(PickSlot { slot: drag.slot });
+        }
+        return;
+    }
+    if let Ok(mut transform) = transforms.get_mut(drag.entity) {
+        let position = board.geometry.position(&drag.slot) + along;
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+    *dragged = Some(drag);
+}
+
+/// Pick the pressed tile and start dragging it
+fn press_tile(
+    pointer: &Pointer,
+    board: &Board,
+    selection: &Selected,
+    limit: &RunLimit,
+    hammer: &HammerArmed,
+    picks: &mut EventWriter<PickSlot>,
+) -> Option<Drag> {
+    if board.animating || limit.is_reached() {
+        return None;
+    }
+    if hammer.0 {
+        // the hammer also smashes stones, which have no tile
+        if let Some(slot) = board.geometry.slot(pointer.position) {
+            if board.grid.layout().is_open(&slot) {
+                picks.send(PickSlot { slot });
+            }
+        }
+        return None;
+    }
+    let slot = match board.geometry.slot(pointer.position) {
+        Some(slot) if board.grid.has_tile(&slot) => slot,
+        _ => return None,
+    };
+    match selection {
+        Some(selected) if selected == &slot => {}
+        Some(selected) if board.grid.neighbors(selected).contains(&slot) => {
+            // the second click of a switch
+            picks.send(PickSlot { slot });
+            return None;
+        }
+        _ => picks.send(PickSlot { slot: slot.clone() }),
+    }
+
+    Some(Drag {
+        pick_on_release: selection == &Some(slot.clone()),
+        entity: board.entity(&slot),
+        slot,
+        start: pointer.position,
+    })
+}
+
+fn snap_back(commands: &mut Commands, board: &Board, drag: &Drag) {
+    commands
+        .entity(drag.entity)
+        .insert(vec![Move::move_to(board.geometry.position(&drag.slot))]);
+}
@@ -0,0 +1,120 @@
+use crate::animate::Animate;
+use crate::board::{Board, Cauldron, CollectableLook, PickSlot};
+use crate::mode::RunLimit;
+use crate::save::SaveData;
+use crate::undo::TurnUndone;
+use crate::{GameState, SystemLabels};
+use bevy::prelude::*;
+
+const IDLE_SECONDS: f32 = 5.;
+
+pub struct HintPlugin;
+
+/// This plugin shows a possible switch after the player was idle for a while or pressed H
+/// Hints after idling can be turned off in the saved settings
+/// Hints prefer switches that bring ingredients the current recipe still needs
+impl Plugin for HintPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Hint::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(reset_hint.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(
+                        hide_hint
+                            .system()
+                            .after(SystemLabels::ReadInput)
+                            .before(SystemLabels::UserInput),
+                    )
+                    .with_system(show_hint.system().after(SystemLabels::MatchPatterns)),
+            );
+    }
+}
+
+struct Hint {
+    idle: Timer,
+    /// The tiles that are pulsing at the moment with the sprite they had before
+    shown: Option<[(Entity, TextureAtlasSprite); 2]>,
+}
+
+impl Default for Hint {
+    fn default() -> Self {
+        Hint {
+            idle: Timer::from_seconds(IDLE_SECONDS, false),
+            shown: None,
+        }
+    }
+}
+
+fn reset_hint(mut hint: ResMut<Hint>) {
+    *hint = Hint::default();
+}
+
+/// Picking any slot stops the hint and restarts the idle time
+///
+/// Runs before the user input, so the selection can animate a hinted tile again.
+fn hide_hint(
+    mut commands: Commands,
+    mut hint: ResMut<Hint>,
+    mut picks: EventReader<PickSlot>,
+    mut undone: EventReader<TurnUndone>,
+    board: Res<Board>,
+    tiles: Query<(), With<TextureAtlasSprite>>,
+) {
+    if undone.iter().count() > 0 {
+        // the hinted tiles were despawned
+        hint.shown = None;
+    }
+    if picks.iter().next().is_none() && !board.animating {
+        return;
+    }
+    hint.idle.reset();
+    if let Some(shown) = hint.shown.take() {
+        for (entity, sprite) in shown.iter() {
+            // a hinted tile might have been despawned in the meantime
+            if tiles.get(*entity).is_err() {
+                continue;
+            }
+            commands
+                .entity(*entity)
+                .remove::<Animate>()
+                .insert(sprite.clone());
+        }
+    }
+}
+
+fn show_hint(
+    mut commands: Commands,
+    time: Res<Time>,
+    input: Res<Input<KeyCode>>,
+    mut hint: ResMut<Hint>,
+    board: Res<Board>,
+    cauldron: Res<Cauldron>,
+    limit: Res<RunLimit>,
+    save: Res<SaveData>,
+    sprites: Query<&TextureAtlasSprite>,
+) {
+    if hint.shown.is_some() || board.animating || !board.grid.is_settled() || limit.is_reached() {
+        return;
+    }
+    hint.idle.tick(time.delta());
+    let idle = save.settings.hints && hint.idle.finished();
+    if !idle && !input.just_pressed(KeyCode::H) {
+        return;
+    }
+    if let Some((one, two)) = board.find_best_move(&cauldron.missing_ingredients()) {
+        let tiles = [board.entity(&one), board.entity(&two)];
+        let shown = match (sprites.get(tiles[0]), sprites.get(tiles[1])) {
+            (Ok(first), Ok(second)) => [(tiles[0], first.clone()), (tiles[1], second.clone())],
+            _ => return,
+        };
+        for (slot, entity) in [&one, &two].iter().zip(tiles.iter()) {
+            commands.entity(*entity).insert(Animate {
+                loop_animation: true,
+                ..board.grid.get(slot).collectable.get_animation()
+            });
+        }
+        hint.shown = Some(shown);
+    }
+}
@@ -1,6 +1,9 @@
-use crate::board::{Cauldron, Ingredients, Score};
+use crate::board::{Cauldron, CollectableLook, Order, RunClock, Score};
+use crate::items::ItemUsed;
 use crate::loading::{FontAssets, TextureAssets};
 use crate::matcher::Collectable;
+use crate::mode::{RunLimit, RunSettings};
+use crate::undo::TurnUndone;
 use crate::{GameState, SystemLabels};
 use bevy::prelude::*;
 
@@ -8,42 +11,75 @@ pub struct HudPlugin;
 
 impl Plugin for HudPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system_set(SystemSet::on_enter(GameState::Playing).with_system(setup_ui.system()))
-            .add_system_set(
-                SystemSet::on_update(GameState::Playing)
-                    .with_system(
-                        update_recipe
-                            .system()
-                            .after(SystemLabels::DisplayUiForNewRecipe),
-                    )
-                    .with_system(
-                        finished_recipe
-                            .system()
-                            .label(SystemLabels::DisplayUiForNewRecipe),
-                    )
-                    .with_system(update_score.system().after(SystemLabels::Animate)),
-            )
-            .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(remove.system()))
-            .add_event::<FinishedRecipe>();
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Playing)
+                .with_system(setup_ui.system().after(SystemLabels::StartRun)),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(
+                    update_recipe
+                        .system()
+                        .after(SystemLabels::DisplayUiForNewRecipe),
+                )
+                .with_system(
+                    finished_recipe
+                        .system()
+                        .label(SystemLabels::DisplayUiForNewRecipe),
+                )
+                .with_system(update_shelf.system())
+                .with_system(update_score.system().after(SystemLabels::Animate))
+                .with_system(update_limit.system())
+                .with_system(show_combo.system().after(SystemLabels::MatchPatterns))
+                .with_system(show_shuffle.system())
+                .with_system(fade_popups.system()),
+        )
+        .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(remove.system()))
+        .add_event::<FinishedRecipe>()
+        .add_event::<SpoiledRecipe>()
+        .add_event::<Combo>()
+        .add_event::<BoardShuffled>();
     }
 }
 
 pub struct FinishedRecipe;
 
+/// A customer ran out of patience and a new order was placed
+pub struct SpoiledRecipe;
+
+/// A wave of a cascade was taken. Sent for every wave after the first one.
+pub struct Combo {
+    pub multiplier: usize,
+}
+
+/// No switch was left and the board was shuffled for the given coins
+pub struct BoardShuffled {
+    pub cost: usize,
+}
+
+/// Text that floats up and fades out
+struct Popup {
+    timer: Timer,
+}
+
 struct Ui;
 
 fn setup_ui(
     mut commands: Commands,
     cauldron: Res<Cauldron>,
+    clock: Res<RunClock>,
     fonts: Res<FontAssets>,
     textures: Res<TextureAssets>,
+    settings: Res<RunSettings>,
+    limit: Res<RunLimit>,
 ) {
-    display_ingredients(&mut commands, &cauldron, &fonts, &textures);
+    display_orders(&mut commands, &cauldron, clock.seconds, &fonts, &textures);
+    display_shelf(&mut commands, &cauldron, &settings, &fonts, &textures);
     commands
         .spawn_bundle(Text2dBundle {
             text: Text {
                 sections: vec![TextSection {
-                    value: format!("x{}", 0),
+                    value: potions_text(&cauldron, &settings),
                     style: TextStyle {
                         font: fonts.fira_sans.clone(),
                         font_size: 15.,
@@ -75,87 +111,267 @@ fn setup_ui(
         })
         .insert(Ui)
         .insert(MoneyDisplay);
+    if let Some(value) = limit_text(&limit) {
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text {
+                    sections: vec![TextSection {
+                        value,
+                        style: TextStyle {
+                            font: fonts.fira_sans.clone(),
+                            font_size: 20.,
+                            ..Default::default()
+                        },
+                    }],
+                    alignment: Default::default(),
+                },
+                transform: Transform::from_translation(Vec3::new(620., 290., 10.)),
+                ..Text2dBundle::default()
+            })
+            .insert(Ui)
+            .insert(LimitDisplay);
+    }
 }
 
 struct MoneyDisplay;
 struct PotionsCount;
+struct LimitDisplay;
+
+/// Part of the display of the order with the given index
+struct OrderDisplay(usize);
+struct OrderTitle;
+
+struct ShelfTitle;
+/// Shows how many of the collectable are on the shelf
+struct ShelfCount(Collectable);
+
+fn potions_text(cauldron: &Cauldron, settings: &RunSettings) -> String {
+    match settings.potions {
+        Some(potions) => format!("x{}/{}", cauldron.finished_recipes, potions),
+        None => format!("x{}", cauldron.finished_recipes),
+    }
+}
+
+fn limit_text(limit: &RunLimit) -> Option<String> {
+    if let Some(moves) = limit.moves_left {
+        return Some(format!("Moves: {}", moves));
+    }
+    limit.time_left.as_ref().map(|timer| {
+        format!(
+            "Time: {:.0}s",
+            (timer.duration() - timer.elapsed()).as_secs_f32().ceil()
+        )
+    })
+}
+
+fn order_title(order: &Order, now: f32) -> String {
+    format!(
+        "{} ({:.0}s)",
+        order.recipe.name,
+        order.seconds_left(now).ceil()
+    )
+}
+
+fn ingredient_text(order: &Order, collectable: &Collectable) -> String {
+    let amount = order
+        .recipe
+        .ingredients
+        .iter()
+        .find(|ingredient| &ingredient.collectable == collectable)
+        .map(|ingredient| ingredient.amount)
+        .unwrap_or(0);
+    format!(
+        "{}/{}",
+        order.content.get(collectable).unwrap_or(&0),
+        amount
+    )
+}
 
 fn finished_recipe(
     mut commands: Commands,
     mut events: EventReader<FinishedRecipe>,
+    mut spoiled_events: EventReader<SpoiledRecipe>,
+    mut undone_events: EventReader<TurnUndone>,
+    mut item_events: EventReader<ItemUsed>,
     cauldron: Res<Cauldron>,
+    clock: Res<RunClock>,
+    settings: Res<RunSettings>,
     fonts: Res<FontAssets>,
     mut potions_count: Query<&mut Text, (With<Ui>, With<PotionsCount>)>,
     textures: Res<TextureAssets>,
-    recipe_ui_components: Query<Entity, (With<Ui>, With<Collectable>)>,
+    order_displays: Query<Entity, With<OrderDisplay>>,
 ) {
-    for _event in events.iter() {
-        for entity in recipe_ui_components.iter() {
+    // a spoiled order, an undone switch or an item also change the open orders
+    let redraw = events.iter().count()
+        + spoiled_events.iter().count()
+        + undone_events.iter().count()
+        + item_events.iter().count();
+    if redraw > 0 {
+        for entity in order_displays.iter() {
             commands.entity(entity).despawn();
         }
-        display_ingredients(&mut commands, &cauldron, &fonts, &textures);
+        display_orders(&mut commands, &cauldron, clock.seconds, &fonts, &textures);
         if let Ok(mut text) = potions_count.single_mut() {
-            text.sections[0].value = format!("x{}", cauldron.finished_recipes);
+            text.sections[0].value = potions_text(&cauldron, &settings);
         }
     }
 }
 
-fn display_ingredients(
+/// Show every open order on the scroll, the oldest one on top
+fn display_orders(
     commands: &mut Commands,
     cauldron: &Cauldron,
+    now: f32,
     fonts: &FontAssets,
     textures: &TextureAssets,
 ) {
-    let mut index = 0;
-    for ingredient in cauldron.recipe.ingredients.iter() {
-        let mut text_transform = Transform::from_translation(Vec3::new(
-            800. - (index + 1) as f32 * 76. - 18.,
-            430.,
-            10.,
-        ));
-        let mut icon_transform = Transform::from_translation(Vec3::new(
-            800. - (index + 1) as f32 * 76. + 5.,
-            430. + 7.,
-            10.,
-        ));
-        icon_transform.scale = Vec3::new(0.5, 0.5, 0.5);
-        if index == 2 {
-            text_transform.translation.y -= 30.;
-            text_transform.translation.x += 76.;
-            icon_transform.translation.y -= 30.;
-            icon_transform.translation.x += 76.;
+    for (index, order) in cauldron.orders.iter().enumerate() {
+        let top = 480. - index as f32 * 40.;
+        commands
+            .spawn_bundle(Text2dBundle {
+                text: Text {
+                    sections: vec![TextSection {
+                        value: order_title(order, now),
+                        style: TextStyle {
+                            font: fonts.fira_sans.clone(),
+                            font_size: 13.,
+                            color: Color::BLACK,
+                        },
+                    }],
+                    alignment: Default::default(),
+                },
+                transform: Transform::from_translation(Vec3::new(610., top, 10.)),
+                ..Text2dBundle::default()
+            })
+            .insert(Ui)
+            .insert(OrderDisplay(index))
+            .insert(OrderTitle);
+        for (column, ingredient) in order.recipe.ingredients.iter().enumerate() {
+            let left = 615. + column as f32 * 58.;
+            let mut icon_transform =
+                Transform::from_translation(Vec3::new(left + 38., top - 18., 10.));
+            icon_transform.scale = Vec3::new(0.3, 0.3, 0.3);
+            commands
+                .spawn_bundle(Text2dBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: ingredient_text(order, &ingredient.collectable),
+                            style: TextStyle {
+                                font: fonts.fira_sans.clone(),
+                                font_size: 13.,
+                                ..Default::default()
+                            },
+                        }],
+                        alignment: Default::default(),
+                    },
+                    transform: Transform::from_translation(Vec3::new(left, top - 12., 10.)),
+                    ..Text2dBundle::default()
+                })
+                .insert(Ui)
+                .insert(OrderDisplay(index))
+                .insert(ingredient.collectable.clone());
+            commands
+                .spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: ingredient.collectable.get_texture(&textures),
+                    transform: icon_transform,
+                    sprite: TextureAtlasSprite {
+                        index: ingredient.collectable.get_animation().frames - 1,
+                        ..TextureAtlasSprite::default()
+                    },
+                    ..SpriteSheetBundle::default()
+                })
+                .insert(Ui)
+                .insert(OrderDisplay(index));
         }
+    }
+}
+
+/// Show what is on the shelf, with a place for every collectable of the run
+fn display_shelf(
+    commands: &mut Commands,
+    cauldron: &Cauldron,
+    settings: &RunSettings,
+    fonts: &FontAssets,
+    textures: &TextureAssets,
+) {
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text {
+                sections: vec![TextSection {
+                    value: shelf_title(cauldron),
+                    style: TextStyle {
+                        font: fonts.fira_sans.clone(),
+                        font_size: 15.,
+                        ..Default::default()
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            transform: Transform::from_translation(Vec3::new(610., 255., 10.)),
+            ..Text2dBundle::default()
+        })
+        .insert(Ui)
+        .insert(ShelfTitle);
+    for (index, collectable) in settings.collectables.iter().enumerate() {
+        let left = 615. + (index % 3) as f32 * 58.;
+        let top = 232. - (index / 3) as f32 * 26.;
+        let mut icon_transform = Transform::from_translation(Vec3::new(left + 38., top - 6., 10.));
+        icon_transform.scale = Vec3::new(0.3, 0.3, 0.3);
         commands
             .spawn_bundle(Text2dBundle {
                 text: Text {
                     sections: vec![TextSection {
-                        value: format!("{}/{}", 0, ingredient.amount),
+                        value: format!("x{}", cauldron.shelf.count(collectable)),
                         style: TextStyle {
                             font: fonts.fira_sans.clone(),
-                            font_size: 15.,
+                            font_size: 13.,
                             ..Default::default()
                         },
                     }],
                     alignment: Default::default(),
                 },
-                transform: text_transform,
+                transform: Transform::from_translation(Vec3::new(left, top, 10.)),
                 ..Text2dBundle::default()
             })
             .insert(Ui)
-            .insert(ingredient.collectable.clone());
+            .insert(ShelfCount(collectable.clone()));
         commands
             .spawn_bundle(SpriteSheetBundle {
-                texture_atlas: ingredient.collectable.get_texture(&textures),
+                texture_atlas: collectable.get_texture(&textures),
                 transform: icon_transform,
                 sprite: TextureAtlasSprite {
-                    index: ingredient.collectable.get_animation().frames - 1,
+                    index: collectable.get_animation().frames - 1,
                     ..TextureAtlasSprite::default()
                 },
                 ..SpriteSheetBundle::default()
             })
-            .insert(Ui)
-            .insert(ingredient.collectable.clone());
-        index += 1;
+            .insert(Ui);
+    }
+}
+
+fn shelf_title(cauldron: &Cauldron) -> String {
+    format!(
+        "Shelf {}/{}",
+        cauldron.shelf.stored(),
+        cauldron.shelf.capacity()
+    )
+}
+
+fn update_shelf(
+    cauldron: Res<Cauldron>,
+    mut texts: QuerySet<(
+        Query<&mut Text, With<ShelfTitle>>,
+        Query<(&mut Text, &ShelfCount)>,
+    )>,
+) {
+    if !cauldron.is_changed() {
+        return;
+    }
+    if let Ok(mut text) = texts.q0_mut().single_mut() {
+        text.sections[0].value = shelf_title(&cauldron);
+    }
+    for (mut text, count) in texts.q1_mut().iter_mut() {
+        text.sections[0].value = format!("x{}", cauldron.shelf.count(&count.0));
     }
 }
 
@@ -170,28 +386,121 @@ fn update_score(
     }
 }
 
+fn update_limit(limit: Res<RunLimit>, mut display: Query<&mut Text, With<LimitDisplay>>) {
+    if limit.is_changed() {
+        if let (Ok(mut text), Some(value)) = (display.single_mut(), limit_text(&limit)) {
+            if text.sections[0].value != value {
+                text.sections[0].value = value;
+            }
+        }
+    }
+}
+
 fn update_recipe(
     cauldron: Res<Cauldron>,
-    mut text: Query<(&mut Text, &Collectable), (With<Ui>, Without<TextureAtlasSprite>)>,
+    clock: Res<RunClock>,
+    mut texts: Query<(
+        &mut Text,
+        &OrderDisplay,
+        Option<&Collectable>,
+        Option<&OrderTitle>,
+    )>,
+) {
+    let contents_changed = cauldron.is_changed();
+    for (mut text, display, collectable, title) in texts.iter_mut() {
+        // the displays of an order are only redrawn in the frame after it was replaced
+        let order = match cauldron.orders.get(display.0) {
+            Some(order) => order,
+            None => continue,
+        };
+        let value = match (collectable, title) {
+            (Some(collectable), _) if contents_changed => ingredient_text(order, collectable),
+            // the countdown in the title changes with the clock
+            (None, Some(_)) => order_title(order, clock.seconds),
+            _ => continue,
+        };
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+fn show_combo(
+    mut commands: Commands,
+    mut events: EventReader<Combo>,
+    fonts: Res<FontAssets>,
+    popups: Query<Entity, With<Popup>>,
 ) {
-    if cauldron.is_changed() || cauldron.is_added() {
-        for (mut text, collectable) in text.iter_mut() {
-            text.sections.first_mut().unwrap().value = format!(
-                "{}/{}",
-                cauldron.content.get(collectable).unwrap_or(&0),
-                cauldron
-                    .recipe
-                    .ingredients
-                    .iter()
-                    .find(|&ingredient| &ingredient.collectable == collectable)
-                    // we need to ignore None for the frame in which a new recipe gets started
-                    .unwrap_or(&Ingredients {
-                        amount: 0,
-                        collectable: collectable.clone()
-                    })
-                    .amount
-            )
+    if let Some(combo) = events.iter().last() {
+        for entity in popups.iter() {
+            commands.entity(entity).despawn();
+        }
+        spawn_popup(
+            &mut commands,
+            format!("Combo x{}", combo.multiplier),
+            &fonts,
+        );
+    }
+}
+
+fn show_shuffle(
+    mut commands: Commands,
+    mut events: EventReader<BoardShuffled>,
+    fonts: Res<FontAssets>,
+    popups: Query<Entity, With<Popup>>,
+) {
+    if let Some(shuffle) = events.iter().last() {
+        for entity in popups.iter() {
+            commands.entity(entity).despawn();
+        }
+        let value = if shuffle.cost > 0 {
+            format!("Shuffle -{}", shuffle.cost)
+        } else {
+            "Shuffle".to_owned()
+        };
+        spawn_popup(&mut commands, value, &fonts);
+    }
+}
+
+fn spawn_popup(commands: &mut Commands, value: String, fonts: &FontAssets) {
+    commands
+        .spawn_bundle(Text2dBundle {
+            text: Text {
+                sections: vec![TextSection {
+                    value,
+                    style: TextStyle {
+                        font: fonts.fira_sans.clone(),
+                        font_size: 40.,
+                        color: Color::rgb(1., 0.85, 0.3),
+                    },
+                }],
+                alignment: Default::default(),
+            },
+            transform: Transform::from_translation(Vec3::new(200., 330., 20.)),
+            ..Text2dBundle::default()
+        })
+        .insert(Ui)
+        .insert(Popup {
+            timer: Timer::from_seconds(1.2, false),
+        });
+}
+
+fn fade_popups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut popups: Query<(Entity, &mut Popup, &mut Text, &mut Transform)>,
+) {
+    for (entity, mut popup, mut text, mut transform) in popups.iter_mut() {
+        popup.timer.tick(time.delta());
+        if popup.timer.finished() {
+            commands.entity(entity).despawn();
+            continue;
         }
+        transform.translation.y += time.delta_seconds() * 20.;
+        text.sections[0]
+            .style
+            .color
+            .set_a(1. - popup.timer.percent());
     }
 }
 
@@ -0,0 +1,271 @@
+use crate::animate::Animate;
+use crate::board::{Board, Cauldron, HammerArmed, PickSlot, RunClock, Selected, TileLook};
+use crate::hud::BoardShuffled;
+use crate::loading::{FontAssets, RecipeAssets, TextureAssets};
+use crate::menu::ButtonMaterials;
+use crate::mode::{RunLimit, RunSettings};
+use crate::random::GameRng;
+use crate::recipe::{Recipe, RecipeBook};
+use crate::replay::Replays;
+use crate::save::SaveData;
+use crate::shop::Item;
+use crate::{GameState, SystemLabels};
+use bevy::prelude::*;
+
+/// Moves added by one [Item::Moves]
+const EXTRA_MOVES: usize = 5;
+/// Use the items in the order of [Item::ALL]
+const ITEM_KEYS: [KeyCode; 4] = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4];
+
+pub struct ItemsPlugin;
+
+/// This plugin lets the player use the items bought in the shop during a run
+/// Owned items get a button in the corner of the screen. The number keys 1 to 4 use them, too.
+/// An armed hammer smashes the next picked slot.
+impl Plugin for ItemsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<ItemUsed>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(setup_items.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(use_item.system())
+                    .with_system(
+                        swing_hammer
+                            .system()
+                            .after(SystemLabels::ReadInput)
+                            .after(SystemLabels::UserInput),
+                    )
+                    .with_system(show_items.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing).with_system(remove_items.system()),
+            );
+    }
+}
+
+/// An item was used up
+pub struct ItemUsed {
+    pub item: Item,
+}
+
+struct ItemsUi;
+
+struct ItemButton(Item);
+
+fn item_text(item: Item, save: &SaveData) -> String {
+    let key = Item::ALL
+        .iter()
+        .position(|other| other == &item)
+        .unwrap_or(0)
+        + 1;
+    format!("{}: {} ({})", key, item.label(), save.inventory.count(item))
+}
+
+fn setup_items(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_materials: Res<ButtonMaterials>,
+    save: Res<SaveData>,
+    replays: Res<Replays>,
+    mut hammer: ResMut<HammerArmed>,
+) {
+    hammer.0 = false;
+    let owned: Vec<Item> = Item::ALL
+        .iter()
+        .copied()
+        .filter(|item| save.inventory.count(*item) > 0)
+        .collect();
+    if owned.is_empty() || replays.is_playing() {
+        return;
+    }
+    for (index, item) in owned.into_iter().enumerate() {
+        commands
+            .spawn_bundle(ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(150.0), Val::Px(28.0)),
+                    margin: Rect::all(Val::Auto),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        right: Val::Px(10.0),
+                        top: Val::Px(50.0 + index as f32 * 34.),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                material: button_materials.normal.clone(),
+                ..Default::default()
+            })
+            .insert(ItemButton(item))
+            .insert(ItemsUi)
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(TextBundle {
+                        text: Text {
+                            sections: vec![TextSection {
+                                value: item_text(item, &save),
+                                style: TextStyle {
+                                    font: font_assets.fira_sans.clone(),
+                                    font_size: 18.0,
+                                    color: Color::rgb(0.9, 0.9, 0.9),
+                                },
+                            }],
+                            alignment: Default::default(),
+                        },
+                        ..Default::default()
+                    })
+                    .insert(ItemsUi);
+            });
+    }
+}
+
+fn use_item(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    interaction_query: Query<(&Interaction, &ItemButton), Changed<Interaction>>,
+    textures: Res<TextureAssets>,
+    orders: (Res<RecipeAssets>, Res<Assets<RecipeBook>>, Res<RunClock>),
+    settings: Res<RunSettings>,
+    replays: Res<Replays>,
+    mut board: ResMut<Board>,
+    mut cauldron: ResMut<Cauldron>,
+    mut limit: ResMut<RunLimit>,
+    mut rng: ResMut<GameRng>,
+    mut save: ResMut<SaveData>,
+    mut selection: ResMut<Selected>,
+    mut hammer: ResMut<HammerArmed>,
+    mut shuffled: EventWriter<BoardShuffled>,
+    mut used: EventWriter<ItemUsed>,
+) {
+    let item = interaction_query
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, button)| button.0)
+        .or_else(|| {
+            ITEM_KEYS
+                .iter()
+                .zip(Item::ALL.iter())
+                .find(|(key, _)| keys.just_pressed(**key))
+                .map(|(_, item)| *item)
+        });
+    let item = match item {
+        Some(item) if save.inventory.count(item) > 0 => item,
+        _ => return,
+    };
+    if replays.is_playing() || limit.is_reached() || board.animating || !board.grid.is_settled() {
+        return;
+    }
+
+    let used_up = match item {
+        Item::Shuffle => {
+            let done = board.shuffle(&mut rng.board, &mut commands, &textures);
+            if done {
+                shuffled.send(BoardShuffled { cost: 0 });
+            }
+            done
+        }
+        Item::Hammer => {
+            // the hammer is only used up once it hits something
+            hammer.0 = !hammer.0;
+            if let Some(selected) = selection.take() {
+                commands
+                    .entity(board.entity(&selected))
+                    .remove::<Animate>()
+                    .insert(board.grid.get(&selected).sprite());
+            }
+            false
+        }
+        Item::Reroll => {
+            let (recipe_assets, recipe_books, clock) = orders;
+            let recipe = Recipe::next(
+                recipe_books.get(&recipe_assets.book),
+                &settings.collectables,
+                &settings.weights,
+                cauldron.finished_recipes,
+                &mut rng.recipes,
+            );
+            cauldron.replace_first_order(recipe, clock.seconds);
+            true
+        }
+        Item::Moves => limit.add_moves(EXTRA_MOVES),
+    };
+    if used_up {
+        save.inventory.take(item);
+        save.store();
+        used.send(ItemUsed { item });
+    }
+}
+
+/// Smash the picked slot if the hammer is armed
+fn swing_hammer(
+    mut commands: Commands,
+    mut picks: EventReader<PickSlot>,
+    textures: Res<TextureAssets>,
+    mut hammer: ResMut<HammerArmed>,
+    mut board: ResMut<Board>,
+    mut rng: ResMut<GameRng>,
+    mut save: ResMut<SaveData>,
+    mut used: EventWriter<ItemUsed>,
+) {
+    if !hammer.0 {
+        return;
+    }
+    let slot = match picks.iter().last() {
+        Some(PickSlot { slot }) if !board.animating && board.grid.layout().is_open(slot) => {
+            slot.clone()
+        }
+        _ => return,
+    };
+    board.smash(&slot, &mut rng.board, &mut commands, &textures);
+    hammer.0 = false;
+    save.inventory.take(Item::Hammer);
+    save.store();
+    used.send(ItemUsed { item: Item::Hammer });
+}
+
+fn show_items(
+    save: Res<SaveData>,
+    hammer: Res<HammerArmed>,
+    button_materials: Res<ButtonMaterials>,
+    mut buttons: Query<(
+        &ItemButton,
+        &Interaction,
+        &mut Handle<ColorMaterial>,
+        &Children,
+    )>,
+    mut texts: Query<&mut Text>,
+) {
+    for (button, interaction, mut material, children) in buttons.iter_mut() {
+        let wanted = if button.0 == Item::Hammer && hammer.0 {
+            &button_materials.armed
+        } else if *interaction == Interaction::Hovered {
+            &button_materials.hovered
+        } else {
+            &button_materials.normal
+        };
+        if *material != *wanted {
+            *material = wanted.clone();
+        }
+        if save.is_changed() {
+            for child in children.iter() {
+                if let Ok(mut text) = texts.get_mut(*child) {
+                    text.sections[0].value = item_text(button.0, &save);
+                }
+            }
+        }
+    }
+}
+
+fn remove_items(
+    mut commands: Commands,
+    elements: Query<Entity, With<ItemsUi>>,
+    mut hammer: ResMut<HammerArmed>,
+) {
+    hammer.0 = false;
+    for entity in elements.iter() {
+        commands.entity(entity).despawn();
+    }
+}
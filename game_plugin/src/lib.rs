@@ -1,18 +1,53 @@
+// systems take every resource and query they use as an argument
+#![allow(clippy::too_many_arguments, clippy::type_complexity)]
+
 mod animate;
 mod audio;
 mod board;
+mod camera;
+mod campaign;
+mod cursor;
+mod drag;
+mod hint;
 mod hud;
+mod items;
 mod loading;
 mod lost;
-mod matcher;
+pub mod matcher;
 mod menu;
+mod mode;
+mod pause;
+pub mod random;
+mod recipe;
+mod replay;
+mod save;
+mod settings;
+mod shop;
+pub mod simulation;
+mod storage;
+mod undo;
 
 use crate::animate::AnimatePlugin;
 use crate::audio::InternalAudioPlugin;
 use crate::board::BoardPlugin;
+use crate::camera::CameraPlugin;
+use crate::campaign::CampaignPlugin;
+use crate::cursor::CursorPlugin;
+use crate::drag::DragPlugin;
+use crate::hint::HintPlugin;
 use crate::hud::HudPlugin;
+use crate::items::ItemsPlugin;
 use crate::loading::LoadingPlugin;
 use crate::menu::MenuPlugin;
+use crate::mode::ModePlugin;
+use crate::pause::PausePlugin;
+use crate::random::RandomPlugin;
+use crate::recipe::RecipePlugin;
+use crate::replay::ReplayPlugin;
+use crate::save::SavePlugin;
+use crate::settings::SettingsPlugin;
+use crate::shop::ShopPlugin;
+use crate::undo::UndoPlugin;
 
 use crate::lost::LostPlugin;
 use bevy::app::AppBuilder;
@@ -24,8 +59,12 @@ use bevy::prelude::*;
 enum GameState {
     Loading,
     Playing,
+    Paused,
     Menu,
     Lost,
+    LevelSelect,
+    Settings,
+    Shop,
 }
 
 #[derive(SystemLabel, Clone, Hash, Debug, Eq, PartialEq)]
@@ -33,6 +72,9 @@ pub enum SystemLabels {
     Animate,
     DisplayUiForNewRecipe,
     MatchPatterns,
+    ReadInput,
+    RecordRun,
+    StartRun,
     UserInput,
 }
 
@@ -41,12 +83,27 @@ pub struct GamePlugin;
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_state(GameState::Loading)
+            .add_plugin(RandomPlugin)
+            .add_plugin(SavePlugin)
+            .add_plugin(RecipePlugin)
+            .add_plugin(CampaignPlugin)
             .add_plugin(LoadingPlugin)
+            .add_plugin(CameraPlugin)
             .add_plugin(MenuPlugin)
+            .add_plugin(SettingsPlugin)
+            .add_plugin(ShopPlugin)
             .add_plugin(InternalAudioPlugin)
+            .add_plugin(ModePlugin)
             .add_plugin(BoardPlugin)
+            .add_plugin(CursorPlugin)
+            .add_plugin(DragPlugin)
             .add_plugin(AnimatePlugin)
             .add_plugin(HudPlugin)
+            .add_plugin(HintPlugin)
+            .add_plugin(UndoPlugin)
+            .add_plugin(ItemsPlugin)
+            .add_plugin(ReplayPlugin)
+            .add_plugin(PausePlugin)
             .add_plugin(LostPlugin);
 
         #[cfg(debug_assertions)]
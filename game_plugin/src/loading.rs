@@ -1,3 +1,5 @@
+use crate::campaign::Campaign;
+use crate::recipe::RecipeBook;
 use crate::GameState;
 use bevy::prelude::*;
 use bevy_asset_loader::{AssetCollection, AssetLoader};
@@ -14,6 +16,8 @@ impl Plugin for LoadingPlugin {
             .with_collection::<FontAssets>()
             .with_collection::<AudioAssets>()
             .with_collection::<RawTextureAssets>()
+            .with_collection::<RecipeAssets>()
+            .with_collection::<CampaignAssets>()
             .init_resource::<TextureAssets>()
             .build(app);
     }
@@ -44,6 +48,18 @@ pub struct AudioAssets {
     pub cooking: Handle<AudioSource>,
 }
 
+#[derive(AssetCollection)]
+pub struct RecipeAssets {
+    #[asset(path = "data/potions.recipes")]
+    pub book: Handle<RecipeBook>,
+}
+
+#[derive(AssetCollection)]
+pub struct CampaignAssets {
+    #[asset(path = "data/campaign.levels")]
+    pub campaign: Handle<Campaign>,
+}
+
 #[derive(AssetCollection, Clone)]
 pub struct RawTextureAssets {
     #[asset(path = "textures/eye_sheet.png")]
@@ -68,6 +84,10 @@ pub struct RawTextureAssets {
     pub scroll: Handle<Texture>,
     #[asset(path = "textures/yellow.png")]
     pub yellow: Handle<Texture>,
+    #[asset(path = "textures/red.png")]
+    pub red: Handle<Texture>,
+    #[asset(path = "textures/green.png")]
+    pub green: Handle<Texture>,
 }
 
 pub struct TextureAssets {
@@ -79,6 +99,8 @@ pub struct TextureAssets {
     pub jar: Handle<TextureAtlas>,
     pub teeth: Handle<TextureAtlas>,
     pub yellow: Handle<TextureAtlas>,
+    pub red: Handle<TextureAtlas>,
+    pub green: Handle<TextureAtlas>,
     pub cauldron: Handle<TextureAtlas>,
 }
 
@@ -135,6 +157,18 @@ impl FromWorld for TextureAssets {
                 6,
                 1,
             )),
+            red: texture_atlases.add(TextureAtlas::from_grid(
+                raw_textures.red.clone(),
+                Vec2::new(64., 64.),
+                6,
+                1,
+            )),
+            green: texture_atlases.add(TextureAtlas::from_grid(
+                raw_textures.green.clone(),
+                Vec2::new(64., 64.),
+                6,
+                1,
+            )),
             cauldron: texture_atlases.add(TextureAtlas::from_grid(
                 raw_textures.cauldron_sheet.clone(),
                 Vec2::new(192., 192.),
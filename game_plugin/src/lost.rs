@@ -1,44 +1,93 @@
+use crate::audio::AudioEffect;
 use crate::board::{Cauldron, Score};
-use crate::loading::FontAssets;
-use crate::GameState;
+use crate::loading::{AudioAssets, FontAssets};
+use crate::menu::ButtonMaterials;
+use crate::mode::RunSettings;
+use crate::random::GameRng;
+use crate::save::LastRun;
+use crate::{GameState, SystemLabels};
 use bevy::prelude::*;
+use std::ops::Deref;
 
 pub struct LostPlugin;
 
+/// This plugin ends runs and shows how the last run went
+/// Systems that want to end a run send an [EndRun] event
 impl Plugin for LostPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.init_resource::<ButtonMaterials>()
-            .add_system_set(SystemSet::on_enter(GameState::Lost).with_system(setup_menu.system()))
+        app.insert_resource(RunOutcome::GaveUp)
+            .add_event::<EndRun>()
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(end_run.system()))
             .add_system_set(
-                SystemSet::on_update(GameState::Lost).with_system(click_play_button.system()),
+                SystemSet::on_enter(GameState::Lost)
+                    .with_system(setup_menu.system().after(SystemLabels::RecordRun)),
             )
-            .add_system_set(SystemSet::on_exit(GameState::Lost).with_system(reset.system()));
+            .add_system_set(
+                SystemSet::on_update(GameState::Lost).with_system(click_play_button.system()),
+            );
     }
 }
 
-struct ButtonMaterials {
-    normal: Handle<ColorMaterial>,
-    hovered: Handle<ColorMaterial>,
+/// Why the last run ended
+#[derive(Clone, Debug, PartialEq)]
+pub enum RunOutcome {
+    /// There was no switch left that would match a pattern
+    NoSwitchLeft,
+    GaveUp,
+    OutOfMoves,
+    OutOfTime,
+    LevelCompleted {
+        stars: usize,
+    },
 }
 
-impl FromWorld for ButtonMaterials {
-    fn from_world(world: &mut World) -> Self {
-        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
-        ButtonMaterials {
-            normal: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
-            hovered: materials.add(Color::rgb(0.25, 0.25, 0.25).into()),
+impl RunOutcome {
+    fn title(&self) -> String {
+        match self {
+            RunOutcome::NoSwitchLeft => "No switch left".to_owned(),
+            RunOutcome::GaveUp => "You gave up".to_owned(),
+            RunOutcome::OutOfMoves => "Out of moves".to_owned(),
+            RunOutcome::OutOfTime => "Out of time".to_owned(),
+            RunOutcome::LevelCompleted { stars } => format!("Level complete\n{}/3 stars", stars),
         }
     }
 }
 
+/// End the current run
+pub struct EndRun {
+    pub outcome: RunOutcome,
+}
+
+fn end_run(
+    mut events: EventReader<EndRun>,
+    mut outcome: ResMut<RunOutcome>,
+    mut state: ResMut<State<GameState>>,
+    mut audio_effect: EventWriter<AudioEffect>,
+    audio_assets: Res<AudioAssets>,
+) {
+    let mut ended = None;
+    for event in events.iter() {
+        // completing a level wins over running out of moves in the same frame
+        if ended.is_none() || matches!(event.outcome, RunOutcome::LevelCompleted { .. }) {
+            ended = Some(event.outcome.clone());
+        }
+    }
+    if let Some(ended) = ended {
+        let sound = match ended {
+            RunOutcome::LevelCompleted { .. } => audio_assets.potion_complete.clone(),
+            _ => audio_assets.lost.clone(),
+        };
+        audio_effect.send(AudioEffect { handle: sound });
+        *outcome = ended;
+        state.set(GameState::Lost).unwrap();
+    }
+}
+
 struct Lost;
 
 struct PlayButton;
 
-fn reset(mut cauldron: ResMut<Cauldron>, mut score: ResMut<Score>) {
-    *cauldron = Cauldron::new();
-    score.money = 0;
-}
+struct MenuButton;
 
 fn setup_menu(
     mut commands: Commands,
@@ -46,10 +95,20 @@ fn setup_menu(
     button_materials: Res<ButtonMaterials>,
     cauldron: Res<Cauldron>,
     score: Res<Score>,
+    rng: Res<GameRng>,
+    outcome: Res<RunOutcome>,
+    settings: Res<RunSettings>,
+    last_run: Res<LastRun>,
 ) {
-    commands
-        .spawn_bundle(UiCameraBundle::default())
-        .insert(Lost);
+    let title = match (settings.level, outcome.deref()) {
+        (Some(_), RunOutcome::LevelCompleted { .. }) | (None, _) => outcome.title(),
+        (Some(_), _) => format!("Level failed\n{}", outcome.title()),
+    };
+    let best = match last_run.previous_best {
+        _ if last_run.is_new_best => "New best score!".to_owned(),
+        Some(best) => format!("Best: {}", best),
+        None => "".to_owned(),
+    };
     commands
         .spawn_bundle(ButtonBundle {
             style: Style {
@@ -88,6 +147,44 @@ fn setup_menu(
                 })
                 .insert(Lost);
         });
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(120.0), Val::Px(50.0)),
+                margin: Rect::all(Val::Auto),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(70.0),
+                    top: Val::Px(150.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .insert(MenuButton)
+        .insert(Lost)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: "Menu".to_string(),
+                            style: TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 40.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(Lost);
+        });
     commands
         .spawn_bundle(NodeBundle {
             style: Style {
@@ -98,7 +195,7 @@ fn setup_menu(
                 position_type: PositionType::Absolute,
                 position: Rect {
                     right: Val::Px(70.0),
-                    top: Val::Px(220.0),
+                    top: Val::Px(230.0),
                     ..Default::default()
                 },
                 ..Default::default()
@@ -116,8 +213,12 @@ fn setup_menu(
                     text: Text {
                         sections: vec![TextSection {
                             value: format!(
-                                "You brew enough\nPotions to let\nevil win {} times\n\n\nCoins: {}",
-                                cauldron.finished_recipes, score.money
+                                "{}\n\nYou brew enough\nPotions to let\nevil win {} times\n\n\nCoins: {}\n{}\n\nSeed: {}",
+                                title,
+                                cauldron.finished_recipes,
+                                score.money,
+                                best,
+                                rng.seed()
                             ),
                             style: TextStyle {
                                 font: font_assets.fira_sans.clone(),
@@ -138,18 +239,26 @@ fn click_play_button(
     button_materials: Res<ButtonMaterials>,
     mut state: ResMut<State<GameState>>,
     mut interaction_query: Query<
-        (&Interaction, &mut Handle<ColorMaterial>),
+        (
+            &Interaction,
+            &mut Handle<ColorMaterial>,
+            Option<&MenuButton>,
+        ),
         (Changed<Interaction>, With<Button>),
     >,
     menu_elements: Query<Entity, With<Lost>>,
 ) {
-    for (interaction, mut material) in interaction_query.iter_mut() {
+    for (interaction, mut material, menu_button) in interaction_query.iter_mut() {
         match *interaction {
             Interaction::Clicked => {
                 for entity in menu_elements.iter() {
                     commands.entity(entity).despawn();
                 }
-                state.set(GameState::Playing).unwrap();
+                if menu_button.is_some() {
+                    state.set(GameState::Menu).unwrap();
+                } else {
+                    state.set(GameState::Playing).unwrap();
+                }
             }
             Interaction::Hovered => {
                 *material = button_materials.hovered.clone();
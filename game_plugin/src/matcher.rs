@@ -1,29 +1,226 @@
-use crate::animate::Animate;
-use crate::loading::TextureAssets;
-use bevy::prelude::*;
-use rand::distributions::Standard;
-use rand::prelude::Distribution;
+use rand::seq::SliceRandom;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::convert::TryFrom;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Pattern {
+    /// At least three equal collectables in one row or column
     Line { slots: Vec<Slot> },
+    /// A vertical and a horizontal line sharing the last slot of both
+    LShape { slots: Vec<Slot> },
+    /// A vertical and a horizontal line sharing the last slot of one and an inner slot of the other
+    TShape { slots: Vec<Slot> },
+    /// A vertical and a horizontal line sharing an inner slot of both
+    Cross { slots: Vec<Slot> },
+    /// More than two crossing lines, like an H or an E
+    Cluster { slots: Vec<Slot> },
 }
 
-#[derive(Clone, Debug)]
-pub struct SlotContent {
-    pub entity: Entity,
+impl Pattern {
+    pub fn slots(&self) -> &[Slot] {
+        match self {
+            Pattern::Line { slots }
+            | Pattern::LShape { slots }
+            | Pattern::TShape { slots }
+            | Pattern::Cross { slots }
+            | Pattern::Cluster { slots } => slots,
+        }
+    }
+
+    pub fn into_slots(self) -> Vec<Slot> {
+        match self {
+            Pattern::Line { slots }
+            | Pattern::LShape { slots }
+            | Pattern::TShape { slots }
+            | Pattern::Cross { slots }
+            | Pattern::Cluster { slots } => slots,
+        }
+    }
+
+    /// The special tile that this pattern leaves behind
+    pub fn special(&self) -> Option<Special> {
+        match self {
+            Pattern::Line { slots } if slots.len() >= 5 => Some(Special::ColourWipe),
+            Pattern::Line { slots } if slots.len() == 4 => {
+                if slots[0].row == slots[1].row {
+                    Some(Special::RowClearer)
+                } else {
+                    Some(Special::ColumnClearer)
+                }
+            }
+            Pattern::Line { .. } => None,
+            // a shape with a line of five is worth as much as that line
+            shape if longest_arm(shape.slots()) >= 5 => Some(Special::ColourWipe),
+            _ => Some(Special::Bomb),
+        }
+    }
+
+    /// Merge vertical and horizontal lines that share a slot into shapes
+    ///
+    /// All lines that are connected by crossings end up in the same shape, so every slot is part
+    /// of at most one pattern. Lines without a crossing line stay lines.
+    pub fn combine(vertical_lines: Vec<Pattern>, horizontal_lines: Vec<Pattern>) -> Vec<Pattern> {
+        let mut groups: Vec<LineGroup> = vertical_lines
+            .into_iter()
+            .map(|line| LineGroup {
+                vertical: vec![line.into_slots()],
+                horizontal: vec![],
+            })
+            .chain(horizontal_lines.into_iter().map(|line| LineGroup {
+                vertical: vec![],
+                horizontal: vec![line.into_slots()],
+            }))
+            .collect();
+        // merging two groups can connect the result to a third one
+        while let Some((one, other)) = (0..groups.len())
+            .flat_map(|one| (one + 1..groups.len()).map(move |other| (one, other)))
+            .find(|&(one, other)| groups[one].crosses(&groups[other]))
+        {
+            let merged = groups.remove(other);
+            groups[one].vertical.extend(merged.vertical);
+            groups[one].horizontal.extend(merged.horizontal);
+        }
+
+        groups.into_iter().map(LineGroup::into_pattern).collect()
+    }
+
+    fn shape(vertical: Vec<Slot>, horizontal: Vec<Slot>) -> Pattern {
+        let shared = vertical
+            .iter()
+            .find(|slot| horizontal.contains(slot))
+            .expect("Lines of a shape need to share a slot")
+            .clone();
+        let is_end =
+            |line: &Vec<Slot>| line.first() == Some(&shared) || line.last() == Some(&shared);
+        let ends = (is_end(&vertical), is_end(&horizontal));
+        let mut slots = vertical;
+        slots.extend(horizontal.into_iter().filter(|slot| slot != &shared));
+
+        match ends {
+            (true, true) => Pattern::LShape { slots },
+            (false, false) => Pattern::Cross { slots },
+            _ => Pattern::TShape { slots },
+        }
+    }
+}
+
+/// Lines that are connected by crossings
+struct LineGroup {
+    vertical: Vec<Vec<Slot>>,
+    horizontal: Vec<Vec<Slot>>,
+}
+
+impl LineGroup {
+    fn slots(&self) -> impl Iterator<Item = &Slot> {
+        self.vertical.iter().chain(self.horizontal.iter()).flatten()
+    }
+
+    fn crosses(&self, other: &LineGroup) -> bool {
+        self.slots()
+            .any(|slot| other.slots().any(|shared| shared == slot))
+    }
+
+    fn into_pattern(mut self) -> Pattern {
+        match (self.vertical.len(), self.horizontal.len()) {
+            (1, 0) => Pattern::Line {
+                slots: self.vertical.remove(0),
+            },
+            (0, 1) => Pattern::Line {
+                slots: self.horizontal.remove(0),
+            },
+            (1, 1) => Pattern::shape(self.vertical.remove(0), self.horizontal.remove(0)),
+            _ => {
+                let mut slots: Vec<Slot> = vec![];
+                for slot in self.vertical.into_iter().chain(self.horizontal).flatten() {
+                    if !slots.contains(&slot) {
+                        slots.push(slot);
+                    }
+                }
+                Pattern::Cluster { slots }
+            }
+        }
+    }
+}
+
+/// The most slots of the pattern that are next to each other in one row or column
+fn longest_arm(slots: &[Slot]) -> usize {
+    let run = |start: &Slot, row_step: usize, column_step: usize| {
+        (0..)
+            .take_while(|&step| {
+                slots.contains(&Slot::new(
+                    start.row + step * row_step,
+                    start.column + step * column_step,
+                ))
+            })
+            .count()
+    };
+    slots
+        .iter()
+        .map(|slot| run(slot, 1, 0).max(run(slot, 0, 1)))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Special tiles are left behind by big patterns and clear more of the board when they are removed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Special {
+    /// Clears its whole row
+    RowClearer,
+    /// Clears its whole column
+    ColumnClearer,
+    /// Clears all slots around it
+    Bomb,
+    /// Is never part of a pattern. Switching it clears every tile of the collectable it was switched with.
+    ColourWipe,
+}
+
+/// Obstacles sit on a slot of the board and have to be broken by matches
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Obstacle {
+    /// Covers the tile in the slot and breaks together with it
+    Ice,
+    /// Holds the tile in place. Matching the tile breaks the chain, but the tile stays.
+    Chain,
+    /// Takes up the slot and blocks falling tiles. Crumbles when a neighboring tile is matched.
+    Stone,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tile {
     pub collectable: Collectable,
+    pub special: Option<Special>,
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord)]
+impl Tile {
+    pub fn matches(&self, other: &Tile) -> bool {
+        self.special != Some(Special::ColourWipe)
+            && other.special != Some(Special::ColourWipe)
+            && self.collectable == other.collectable
+    }
+}
+
+impl From<Collectable> for Tile {
+    fn from(collectable: Collectable) -> Self {
+        Tile {
+            collectable,
+            special: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Deserialize, Serialize)]
 pub struct Slot {
     pub column: usize,
     pub row: usize,
 }
 
 impl Slot {
+    pub fn new(row: usize, column: usize) -> Self {
+        Slot { row, column }
+    }
+
     pub fn walk(&self, row_delta: i64, column_delta: i64) -> Slot {
         Slot {
             row: usize::try_from(self.row as i64 + row_delta)
@@ -34,7 +231,7 @@ impl Slot {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Hash, Eq)]
+#[derive(Clone, Debug, PartialEq, Hash, Eq, Deserialize)]
 pub enum Collectable {
     Eye,
     Tongue,
@@ -44,70 +241,70 @@ pub enum Collectable {
     Jar,
     Teeth,
     Yellow,
+    Red,
+    Green,
 }
 
-impl Distribution<Collectable> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Collectable {
-        match rng.gen_range(0..7) {
-            0 => Collectable::Eye,
-            1 => Collectable::Tongue,
-            2 => Collectable::Frog,
-            3 => Collectable::Heart,
-            4 => Collectable::Spider,
-            5 => Collectable::Jar,
-            _ => Collectable::Teeth,
-        }
+impl Collectable {
+    /// Every collectable that can show up on the board
+    pub fn all() -> Vec<Collectable> {
+        vec![
+            Collectable::Eye,
+            Collectable::Tongue,
+            Collectable::Frog,
+            Collectable::Heart,
+            Collectable::Spider,
+            Collectable::Jar,
+            Collectable::Teeth,
+            Collectable::Yellow,
+            Collectable::Red,
+            Collectable::Green,
+        ]
+    }
+
+    /// The collectables of the game modes
+    pub fn standard() -> Vec<Collectable> {
+        vec![
+            Collectable::Eye,
+            Collectable::Tongue,
+            Collectable::Frog,
+            Collectable::Heart,
+            Collectable::Spider,
+            Collectable::Jar,
+            Collectable::Teeth,
+            Collectable::Yellow,
+        ]
     }
 }
 
-impl Collectable {
-    pub fn get_texture(&self, assets: &TextureAssets) -> Handle<TextureAtlas> {
-        match self {
-            &Collectable::Eye => assets.eye.clone(),
-            &Collectable::Tongue => assets.tongue.clone(),
-            &Collectable::Frog => assets.frog.clone(),
-            &Collectable::Heart => assets.heart.clone(),
-            &Collectable::Spider => assets.spider.clone(),
-            &Collectable::Jar => assets.jar.clone(),
-            &Collectable::Teeth => assets.teeth.clone(),
-            &Collectable::Yellow => assets.yellow.clone(),
-        }
+/// How often each collectable is drawn compared to the others
+///
+/// Collectables without a weight have a weight of one, so a weight of `0.5` makes a collectable
+/// half as common as the rest.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct SpawnWeights(HashMap<Collectable, f32>);
+
+impl SpawnWeights {
+    pub fn new(weights: Vec<(Collectable, f32)>) -> Self {
+        SpawnWeights(weights.into_iter().collect())
     }
 
-    pub fn get_animation(&self) -> Animate {
-        match self {
-            &Collectable::Eye => Animate {
-                frames: 6,
-                loop_animation: false,
-            },
-            &Collectable::Tongue => Animate {
-                frames: 6,
-                loop_animation: true,
-            },
-            &Collectable::Frog => Animate {
-                frames: 6,
-                loop_animation: false,
-            },
-            &Collectable::Heart => Animate {
-                frames: 6,
-                loop_animation: true,
-            },
-            &Collectable::Spider => Animate {
-                frames: 6,
-                loop_animation: false,
-            },
-            &Collectable::Jar => Animate {
-                frames: 4,
-                loop_animation: true,
-            },
-            &Collectable::Teeth => Animate {
-                frames: 4,
-                loop_animation: true,
-            },
-            &Collectable::Yellow => Animate {
-                frames: 6,
-                loop_animation: true,
-            },
-        }
+    pub fn of(&self, collectable: &Collectable) -> f32 {
+        *self.0.get(collectable).unwrap_or(&1.)
+    }
+
+    /// Draw one of the collectables by their weights
+    ///
+    /// Falls back to equal chances if none of the collectables has a positive weight.
+    pub fn choose<'a, R: Rng + ?Sized>(
+        &self,
+        collectables: &'a [Collectable],
+        rng: &mut R,
+    ) -> Option<&'a Collectable> {
+        collectables
+            .choose_weighted(rng, |collectable| self.of(collectable).max(0.))
+            .ok()
+            .or_else(|| collectables.choose(rng))
     }
 }
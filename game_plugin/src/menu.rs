@@ -1,11 +1,16 @@
-use crate::loading::FontAssets;
+use crate::campaign::Campaign;
+use crate::loading::{CampaignAssets, FontAssets};
+use crate::mode::{GameMode, RunSettings};
+use crate::random::GameRng;
+use crate::replay::Replays;
 use crate::GameState;
 use bevy::prelude::*;
 
 pub struct MenuPlugin;
 
-/// This plugin is responsible for the game menu (containing only one button...)
+/// This plugin is responsible for the game menu
 /// The menu is only drawn during the State `GameState::Menu` and is removed when that state is exited
+/// It also provides the [ButtonMaterials] that the buttons of all menus and overlays share.
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<ButtonMaterials>()
@@ -16,9 +21,14 @@ impl Plugin for MenuPlugin {
     }
 }
 
-struct ButtonMaterials {
-    normal: Handle<ColorMaterial>,
-    hovered: Handle<ColorMaterial>,
+/// The materials of the buttons in all menus and overlays
+pub struct ButtonMaterials {
+    pub normal: Handle<ColorMaterial>,
+    pub hovered: Handle<ColorMaterial>,
+    /// Buttons that can't be clicked yet
+    pub locked: Handle<ColorMaterial>,
+    /// Buttons of an action that waits for its target
+    pub armed: Handle<ColorMaterial>,
 }
 
 impl FromWorld for ButtonMaterials {
@@ -27,6 +37,8 @@ impl FromWorld for ButtonMaterials {
         ButtonMaterials {
             normal: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
             hovered: materials.add(Color::rgb(0.25, 0.25, 0.25).into()),
+            locked: materials.add(Color::rgba(0.15, 0.15, 0.15, 0.5).into()),
+            armed: materials.add(Color::rgb(0.45, 0.2, 0.55).into()),
         }
     }
 }
@@ -35,14 +47,27 @@ struct Menu;
 
 struct PlayButton;
 
+struct ModeButton;
+
+/// Shows the mode that the play button starts
+struct ModeText;
+
+struct CampaignButton;
+
+/// Watch the last recorded run again
+struct ReplayButton;
+
+struct SettingsButton;
+
+struct ShopButton;
+
 fn setup_menu(
     mut commands: Commands,
     font_assets: Res<FontAssets>,
     button_materials: Res<ButtonMaterials>,
+    mode: Res<GameMode>,
+    replays: Res<Replays>,
 ) {
-    commands
-        .spawn_bundle(UiCameraBundle::default())
-        .insert(Menu);
     commands
         .spawn_bundle(ButtonBundle {
             style: Style {
@@ -81,6 +106,199 @@ fn setup_menu(
                 })
                 .insert(Menu);
         });
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(180.0), Val::Px(50.0)),
+                margin: Rect::all(Val::Auto),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(40.0),
+                    top: Val::Px(150.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .insert(ModeButton)
+        .insert(Menu)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: mode.label().to_string(),
+                            style: TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 30.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(ModeText)
+                .insert(Menu);
+        });
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(180.0), Val::Px(50.0)),
+                margin: Rect::all(Val::Auto),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(40.0),
+                    top: Val::Px(230.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .insert(CampaignButton)
+        .insert(Menu)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: "Campaign".to_string(),
+                            style: TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 40.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(Menu);
+        });
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(110.0), Val::Px(32.0)),
+                margin: Rect::all(Val::Auto),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .insert(SettingsButton)
+        .insert(Menu)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: "Settings".to_string(),
+                            style: TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 20.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(Menu);
+        });
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(110.0), Val::Px(32.0)),
+                margin: Rect::all(Val::Auto),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(130.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .insert(ShopButton)
+        .insert(Menu)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: "Shop".to_string(),
+                            style: TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 20.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(Menu);
+        });
+    if replays.last.is_some() {
+        commands
+            .spawn_bundle(ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(180.0), Val::Px(50.0)),
+                    margin: Rect::all(Val::Auto),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        right: Val::Px(40.0),
+                        top: Val::Px(290.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                material: button_materials.normal.clone(),
+                ..Default::default()
+            })
+            .insert(ReplayButton)
+            .insert(Menu)
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(TextBundle {
+                        text: Text {
+                            sections: vec![TextSection {
+                                value: "Replay".to_string(),
+                                style: TextStyle {
+                                    font: font_assets.fira_sans.clone(),
+                                    font_size: 30.0,
+                                    color: Color::rgb(0.9, 0.9, 0.9),
+                                },
+                            }],
+                            alignment: Default::default(),
+                        },
+                        ..Default::default()
+                    })
+                    .insert(Menu);
+            });
+    }
     commands
         .spawn_bundle(NodeBundle {
             style: Style {
@@ -91,7 +309,7 @@ fn setup_menu(
                 position_type: PositionType::Absolute,
                 position: Rect {
                     right: Val::Px(70.0),
-                    top: Val::Px(210.0),
+                    top: Val::Px(370.0),
                     ..Default::default()
                 },
                 ..Default::default()
@@ -128,20 +346,83 @@ fn click_play_button(
     mut commands: Commands,
     button_materials: Res<ButtonMaterials>,
     mut state: ResMut<State<GameState>>,
+    mut settings: ResMut<RunSettings>,
+    mut mode: ResMut<GameMode>,
+    mut replays: ResMut<Replays>,
+    mut rng: ResMut<GameRng>,
+    campaign_assets: Res<CampaignAssets>,
+    campaigns: Res<Assets<Campaign>>,
     mut interaction_query: Query<
-        (&Interaction, &mut Handle<ColorMaterial>),
+        (
+            &Interaction,
+            &mut Handle<ColorMaterial>,
+            Option<&CampaignButton>,
+            Option<&ModeButton>,
+            Option<&ReplayButton>,
+            Option<&SettingsButton>,
+            Option<&ShopButton>,
+        ),
         (Changed<Interaction>, With<Button>),
     >,
+    mut mode_text: Query<&mut Text, With<ModeText>>,
     menu_elements: Query<Entity, With<Menu>>,
 ) {
-    for (interaction, mut material) in interaction_query.iter_mut() {
+    for (
+        interaction,
+        mut material,
+        campaign_button,
+        mode_button,
+        replay_button,
+        settings_button,
+        shop_button,
+    ) in interaction_query.iter_mut()
+    {
         match *interaction {
-            Interaction::Clicked => {
+            Interaction::Clicked if mode_button.is_some() => {
+                *mode = mode.next();
+                if let Ok(mut text) = mode_text.single_mut() {
+                    text.sections[0].value = mode.label().to_string();
+                }
+            }
+            // the menu stays below the settings screen
+            Interaction::Clicked if settings_button.is_some() => {
+                *material = button_materials.normal.clone();
+                state.push(GameState::Settings).unwrap();
+            }
+            Interaction::Clicked if replay_button.is_some() => {
+                let replay = match replays.last.clone() {
+                    Some(replay) => replay,
+                    None => continue,
+                };
+                let replayed_settings =
+                    match replay.settings(campaigns.get(&campaign_assets.campaign)) {
+                        Some(replayed_settings) => replayed_settings,
+                        None => {
+                            warn!("The replayed campaign level does not exist");
+                            continue;
+                        }
+                    };
                 for entity in menu_elements.iter() {
                     commands.entity(entity).despawn();
                 }
+                *settings = replayed_settings;
+                rng.play_seed(replay.seed);
+                replays.queue(replay);
                 state.set(GameState::Playing).unwrap();
             }
+            Interaction::Clicked => {
+                for entity in menu_elements.iter() {
+                    commands.entity(entity).despawn();
+                }
+                if campaign_button.is_some() {
+                    state.set(GameState::LevelSelect).unwrap();
+                } else if shop_button.is_some() {
+                    state.set(GameState::Shop).unwrap();
+                } else {
+                    *settings = mode.settings();
+                    state.set(GameState::Playing).unwrap();
+                }
+            }
             Interaction::Hovered => {
                 *material = button_materials.hovered.clone();
             }
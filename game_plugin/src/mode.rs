@@ -0,0 +1,218 @@
+use crate::board::{Board, PlayerSwitch};
+use crate::lost::{EndRun, RunOutcome};
+use crate::matcher::{Collectable, SpawnWeights};
+use crate::simulation::Layout;
+use crate::{GameState, SystemLabels};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub struct ModePlugin;
+
+/// This plugin holds the rules of the current run and ends the run when its limit is reached
+/// The rules are chosen before the run starts, e.g. in the menu or by picking a campaign level
+impl Plugin for ModePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(GameMode::Endless)
+            .insert_resource(RunSettings::endless())
+            .insert_resource(RunLimit::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(start_limit.system().label(SystemLabels::StartRun)),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(count_moves.system().after(SystemLabels::UserInput))
+                    .with_system(tick_time.system())
+                    .with_system(check_limit.system().after(SystemLabels::MatchPatterns)),
+            );
+    }
+}
+
+/// The modes that can be picked in the menu
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum GameMode {
+    /// Play until there is no switch left and no shuffle can be paid
+    Endless,
+    /// Brew as many potions as possible with a limited number of switches
+    Moves,
+    /// Brew as many potions as possible before the time runs out
+    Clock,
+    /// Play without pressure and undo switches
+    Practice,
+}
+
+impl GameMode {
+    pub fn next(&self) -> Self {
+        match self {
+            GameMode::Endless => GameMode::Moves,
+            GameMode::Moves => GameMode::Clock,
+            GameMode::Clock => GameMode::Practice,
+            GameMode::Practice => GameMode::Endless,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            GameMode::Endless => "Endless",
+            GameMode::Moves => "30 Moves",
+            GameMode::Clock => "3 Minutes",
+            GameMode::Practice => "Practice",
+        }
+    }
+
+    pub fn settings(&self) -> RunSettings {
+        match self {
+            GameMode::Endless => RunSettings::endless(),
+            // the limit ends these runs anyway, so shuffles are free
+            GameMode::Moves => RunSettings {
+                name: self.label().to_owned(),
+                mode: Some(*self),
+                limit: Some(Limit::Moves(30)),
+                shuffle_cost: Some(0),
+                ..RunSettings::endless()
+            },
+            GameMode::Clock => RunSettings {
+                name: self.label().to_owned(),
+                mode: Some(*self),
+                limit: Some(Limit::Time(180.)),
+                shuffle_cost: Some(0),
+                ..RunSettings::endless()
+            },
+            GameMode::Practice => RunSettings {
+                name: self.label().to_owned(),
+                mode: Some(*self),
+                shuffle_cost: Some(0),
+                orders: 2,
+                undo: true,
+                ..RunSettings::endless()
+            },
+        }
+    }
+}
+
+/// The rules of the current run
+#[derive(Clone, Debug)]
+pub struct RunSettings {
+    /// Name of the mode or campaign level. High scores are kept per name.
+    pub name: String,
+    pub layout: Layout,
+    /// The collectables that show up on the board and in recipes
+    pub collectables: Vec<Collectable>,
+    /// How often the collectables show up
+    pub weights: SpawnWeights,
+    pub limit: Option<Limit>,
+    /// Coins to pay for shuffling the board when no switch is left. Without it the run ends.
+    pub shuffle_cost: Option<usize>,
+    /// Potions to brew to complete the run
+    pub potions: Option<usize>,
+    /// Number of customer orders that are open at the same time, from two to four
+    pub orders: usize,
+    /// The game mode that is played, unless it is a campaign level
+    pub mode: Option<GameMode>,
+    /// Index of the campaign level that is played
+    pub level: Option<usize>,
+    /// Switches can be taken back
+    pub undo: bool,
+}
+
+impl RunSettings {
+    /// Play on the full board for as long as the shuffles can be paid
+    pub fn endless() -> Self {
+        RunSettings {
+            name: GameMode::Endless.label().to_owned(),
+            layout: Layout::rectangle(8, 9),
+            collectables: Collectable::standard(),
+            weights: SpawnWeights::new(vec![(Collectable::Yellow, 0.5)]),
+            limit: None,
+            shuffle_cost: Some(50),
+            potions: None,
+            orders: 3,
+            mode: Some(GameMode::Endless),
+            level: None,
+            undo: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum Limit {
+    /// Number of switches the player can make
+    Moves(usize),
+    /// Seconds until the run ends
+    Time(f32),
+}
+
+/// What is left of the limit of the current run
+#[derive(Clone, Default)]
+pub struct RunLimit {
+    pub moves_left: Option<usize>,
+    pub time_left: Option<Timer>,
+}
+
+impl RunLimit {
+    pub fn new(limit: Option<Limit>) -> Self {
+        match limit {
+            Some(Limit::Moves(moves)) => RunLimit {
+                moves_left: Some(moves),
+                time_left: None,
+            },
+            Some(Limit::Time(seconds)) => RunLimit {
+                moves_left: None,
+                time_left: Some(Timer::from_seconds(seconds, false)),
+            },
+            None => RunLimit::default(),
+        }
+    }
+
+    /// Allow more switches. Returns false if the run has no move limit.
+    pub fn add_moves(&mut self, moves: usize) -> bool {
+        match self.moves_left.as_mut() {
+            Some(moves_left) => {
+                *moves_left += moves;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The player can not make any more switches
+    pub fn is_reached(&self) -> bool {
+        self.moves_left == Some(0)
+            || self
+                .time_left
+                .as_ref()
+                .map_or(false, |timer| timer.finished())
+    }
+}
+
+fn start_limit(settings: Res<RunSettings>, mut limit: ResMut<RunLimit>) {
+    *limit = RunLimit::new(settings.limit);
+}
+
+fn count_moves(mut switches: EventReader<PlayerSwitch>, mut limit: ResMut<RunLimit>) {
+    for _switch in switches.iter() {
+        if let Some(moves) = limit.moves_left.as_mut() {
+            *moves = moves.saturating_sub(1);
+        }
+    }
+}
+
+fn tick_time(time: Res<Time>, mut limit: ResMut<RunLimit>) {
+    if let Some(timer) = limit.time_left.as_mut() {
+        timer.tick(time.delta());
+    }
+}
+
+/// End the run once the limit is reached and the last switch is fully played out
+fn check_limit(limit: Res<RunLimit>, board: Res<Board>, mut end_run: EventWriter<EndRun>) {
+    if !limit.is_reached() || board.animating || !board.grid.is_settled() {
+        return;
+    }
+    end_run.send(EndRun {
+        outcome: if limit.moves_left.is_some() {
+            RunOutcome::OutOfMoves
+        } else {
+            RunOutcome::OutOfTime
+        },
+    });
+}
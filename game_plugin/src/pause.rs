@@ -0,0 +1,209 @@
+use crate::audio::Ducking;
+use crate::loading::FontAssets;
+use crate::menu::ButtonMaterials;
+use crate::GameState;
+use bevy::prelude::*;
+
+pub struct PausePlugin;
+
+/// This plugin pauses a run when Escape or the start button of a gamepad is pressed
+/// `GameState::Paused` is pushed on top of `GameState::Playing`, so all systems of the run stop
+/// until it is popped again. The board is dimmed by an overlay with the pause menu.
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<PauseMaterials>()
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(pause.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Paused).with_system(setup_pause.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Paused).with_system(click_pause_button.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Paused).with_system(remove_pause.system()),
+            );
+    }
+}
+
+struct PauseMaterials {
+    overlay: Handle<ColorMaterial>,
+}
+
+impl FromWorld for PauseMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        PauseMaterials {
+            overlay: materials.add(Color::rgba(0., 0., 0., 0.6).into()),
+        }
+    }
+}
+
+struct PauseUi;
+
+#[derive(Clone, Copy)]
+enum PauseButton {
+    Resume,
+    Restart,
+    Settings,
+    Quit,
+}
+
+impl PauseButton {
+    fn label(&self) -> &'static str {
+        match self {
+            PauseButton::Resume => "Resume",
+            PauseButton::Restart => "Restart",
+            PauseButton::Settings => "Settings",
+            PauseButton::Quit => "Menu",
+        }
+    }
+}
+
+/// Escape or the start button was pressed
+///
+/// The press is consumed, so the state on the other side of the transition does not see it.
+fn pressed_pause(
+    keys: &mut Input<KeyCode>,
+    gamepads: &Gamepads,
+    gamepad_buttons: &mut Input<GamepadButton>,
+) -> bool {
+    let mut pressed = false;
+    if keys.just_pressed(KeyCode::Escape) {
+        keys.reset(KeyCode::Escape);
+        pressed = true;
+    }
+    for gamepad in gamepads.iter() {
+        let start = GamepadButton(*gamepad, GamepadButtonType::Start);
+        if gamepad_buttons.just_pressed(start) {
+            gamepad_buttons.reset(start);
+            pressed = true;
+        }
+    }
+
+    pressed
+}
+
+fn pause(
+    mut keys: ResMut<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    mut gamepad_buttons: ResMut<Input<GamepadButton>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if pressed_pause(&mut keys, &gamepads, &mut gamepad_buttons) {
+        state.push(GameState::Paused).unwrap();
+    }
+}
+
+fn setup_pause(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    materials: Res<PauseMaterials>,
+    button_materials: Res<ButtonMaterials>,
+    mut ducking: ResMut<Ducking>,
+) {
+    ducking.0 = true;
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            material: materials.overlay.clone(),
+            ..Default::default()
+        })
+        .insert(PauseUi)
+        .with_children(|parent| {
+            let buttons = [
+                PauseButton::Resume,
+                PauseButton::Restart,
+                PauseButton::Settings,
+                PauseButton::Quit,
+            ];
+            for (index, button) in buttons.iter().enumerate() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(180.0), Val::Px(50.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            position_type: PositionType::Absolute,
+                            position: Rect {
+                                left: Val::Px(310.0),
+                                top: Val::Px(150.0 + index as f32 * 70.),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        material: button_materials.normal.clone(),
+                        ..Default::default()
+                    })
+                    .insert(*button)
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text {
+                                sections: vec![TextSection {
+                                    value: button.label().to_string(),
+                                    style: TextStyle {
+                                        font: font_assets.fira_sans.clone(),
+                                        font_size: 40.0,
+                                        color: Color::rgb(0.9, 0.9, 0.9),
+                                    },
+                                }],
+                                alignment: Default::default(),
+                            },
+                            ..Default::default()
+                        });
+                    });
+            }
+        });
+}
+
+fn click_pause_button(
+    button_materials: Res<ButtonMaterials>,
+    mut keys: ResMut<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    mut gamepad_buttons: ResMut<Input<GamepadButton>>,
+    mut state: ResMut<State<GameState>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut Handle<ColorMaterial>, &PauseButton),
+        Changed<Interaction>,
+    >,
+) {
+    if pressed_pause(&mut keys, &gamepads, &mut gamepad_buttons) {
+        state.pop().unwrap();
+        return;
+    }
+    for (interaction, mut material, button) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                *material = button_materials.normal.clone();
+                match button {
+                    PauseButton::Resume => state.pop().unwrap(),
+                    // replacing the stack leaves the run, so it starts from scratch
+                    PauseButton::Restart => state.replace(GameState::Playing).unwrap(),
+                    PauseButton::Settings => state.push(GameState::Settings).unwrap(),
+                    PauseButton::Quit => state.replace(GameState::Menu).unwrap(),
+                }
+                return;
+            }
+            Interaction::Hovered => {
+                *material = button_materials.hovered.clone();
+            }
+            Interaction::None => {
+                *material = button_materials.normal.clone();
+            }
+        }
+    }
+}
+
+fn remove_pause(
+    mut commands: Commands,
+    elements: Query<Entity, With<PauseUi>>,
+    mut ducking: ResMut<Ducking>,
+) {
+    ducking.0 = false;
+    for entity in elements.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
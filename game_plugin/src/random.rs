@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{random, SeedableRng};
+
+pub struct RandomPlugin;
+
+/// This plugin makes runs reproducible. All randomness of a run is drawn from [GameRng]
+/// A specific seed can be played by starting the game with `--seed <number>`
+impl Plugin for RandomPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(GameRng::new(seed_from_arguments()));
+    }
+}
+
+/// Random number generators for one run
+///
+/// The board and the recipes draw from their own generator. This way the recipes do not depend
+/// on how fast animations are played compared to when new ingredients are needed.
+pub struct GameRng {
+    seed: u64,
+    fixed_seed: Option<u64>,
+    /// Used once by the next run instead of the fixed or a random seed
+    next_seed: Option<u64>,
+    pub board: StdRng,
+    pub recipes: StdRng,
+}
+
+impl GameRng {
+    /// Every run will use the given seed. Without a fixed seed every run gets a random one.
+    pub fn new(fixed_seed: Option<u64>) -> Self {
+        // random seeds stay short enough to be shared by players
+        let seed = fixed_seed.unwrap_or_else(|| u64::from(random::<u32>()));
+        GameRng {
+            fixed_seed,
+            ..GameRng::from_seed(seed)
+        }
+    }
+
+    /// The generators of a run with the given seed
+    pub fn from_seed(seed: u64) -> Self {
+        GameRng {
+            seed,
+            fixed_seed: None,
+            next_seed: None,
+            board: StdRng::seed_from_u64(seed),
+            recipes: StdRng::seed_from_u64(seed.wrapping_add(1)),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The next run uses the given seed, e.g. to replay a recorded run
+    pub fn play_seed(&mut self, seed: u64) {
+        self.next_seed = Some(seed);
+    }
+
+    /// Reseed the generators for the next run
+    pub fn next_run(&mut self) {
+        *self = match self.next_seed {
+            Some(seed) => GameRng {
+                fixed_seed: self.fixed_seed,
+                ..GameRng::from_seed(seed)
+            },
+            None => GameRng::new(self.fixed_seed),
+        };
+    }
+}
+
+fn seed_from_arguments() -> Option<u64> {
+    let mut arguments = std::env::args().skip_while(|argument| argument != "--seed");
+    arguments.next()?;
+    match arguments.next().map(|seed| seed.parse::<u64>()) {
+        Some(Ok(seed)) => Some(seed),
+        _ => {
+            warn!("Expected a positive number after '--seed'");
+            None
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use crate::matcher::{Collectable, SpawnWeights};
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::Deserialize;
+
+/// The orders on the scroll have room for this many ingredients
+pub const MAX_INGREDIENTS: usize = 3;
+
+pub struct RecipePlugin;
+
+/// This plugin registers the [RecipeBook] asset
+/// The recipes and their [Difficulty] curve are defined in `assets/data/potions.recipes` and can be
+/// tuned without recompiling
+impl Plugin for RecipePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<RecipeBook>()
+            .init_asset_loader::<RecipeBookLoader>();
+    }
+}
+
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "5b6f0b43-3c8c-4a55-9c2f-4d7c1d1e8f4a"]
+pub struct RecipeBook {
+    pub recipes: Vec<Recipe>,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+}
+
+/// How the recipes get harder with the potions finished in a run
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Difficulty {
+    /// Potions to finish for the next step on the curves
+    pub potions_per_step: usize,
+    /// Number of ingredients of generated recipes, at most [MAX_INGREDIENTS]
+    pub ingredients: Curve,
+    /// Factor for the amounts of all recipes. Rewards grow with the amounts.
+    pub amounts: Curve,
+    /// Collectables that only show up in recipes now and then
+    pub rare: Vec<Collectable>,
+    /// Chance of a rare collectable for every ingredient of a generated recipe, or for a recipe
+    /// from the book to be one with a rare collectable
+    pub rare_chance: Curve,
+    /// Coins for every ingredient of a generated recipe
+    pub coins_per_ingredient: f32,
+    /// Factor for the coins of a rare ingredient
+    pub rare_bonus: f32,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty {
+            potions_per_step: 3,
+            ingredients: Curve {
+                start: 2.,
+                per_step: 0.5,
+                max: 3.,
+            },
+            amounts: Curve {
+                start: 1.,
+                per_step: 0.15,
+                max: 2.,
+            },
+            rare: vec![],
+            rare_chance: Curve {
+                start: 0.,
+                per_step: 0.1,
+                max: 0.5,
+            },
+            coins_per_ingredient: 4.5,
+            rare_bonus: 2.,
+        }
+    }
+}
+
+impl Difficulty {
+    /// The step on the curves after the given number of finished potions
+    fn step(&self, finished_potions: usize) -> usize {
+        finished_potions / self.potions_per_step.max(1)
+    }
+
+    fn is_rare(&self, collectable: &Collectable) -> bool {
+        self.rare.contains(collectable)
+    }
+}
+
+/// A value that grows linearly with every step until it reaches its maximum
+#[derive(Clone, Debug, Deserialize)]
+pub struct Curve {
+    pub start: f32,
+    pub per_step: f32,
+    pub max: f32,
+}
+
+impl Curve {
+    pub fn at(&self, step: usize) -> f32 {
+        (self.start + self.per_step * step as f32).min(self.max)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Recipe {
+    pub name: String,
+    pub ingredients: Vec<Ingredients>,
+    pub reward: usize,
+    /// Seconds the customer waits for the potion before leaving without paying
+    #[serde(default)]
+    pub time_limit: Option<f32>,
+}
+
+impl Recipe {
+    /// Pick a random recipe from the book that can be brewed with the given collectables
+    ///
+    /// Falls back to a randomly generated recipe if there is no book or no fitting recipe in it.
+    /// The recipe is as hard as the [Difficulty] of the book is after the finished potions.
+    pub fn next<R: Rng + ?Sized>(
+        book: Option<&RecipeBook>,
+        collectables: &[Collectable],
+        weights: &SpawnWeights,
+        finished_potions: usize,
+        rng: &mut R,
+    ) -> Self {
+        let default_difficulty = Difficulty::default();
+        let difficulty = book
+            .map(|book| &book.difficulty)
+            .unwrap_or(&default_difficulty);
+        let step = difficulty.step(finished_potions);
+        let fitting: Vec<&Recipe> = book
+            .map(|book| {
+                book.recipes
+                    .iter()
+                    .filter(|recipe| recipe.can_be_brewed_with(collectables))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let (rare, common): (Vec<&Recipe>, Vec<&Recipe>) = fitting.iter().partition(|recipe| {
+            recipe
+                .ingredients
+                .iter()
+                .any(|ingredient| difficulty.is_rare(&ingredient.collectable))
+        });
+        let candidates = if rare.is_empty() || common.is_empty() {
+            fitting
+        } else if rng.gen_bool(difficulty.rare_chance.at(step).clamp(0., 1.) as f64) {
+            rare
+        } else {
+            common
+        };
+        candidates
+            .choose(rng)
+            .map(|&recipe| recipe.scaled(difficulty.amounts.at(step)))
+            .unwrap_or_else(|| Recipe::build_random(collectables, weights, difficulty, step, rng))
+    }
+
+    /// Generate a recipe from the given collectables for the step on the difficulty curves
+    ///
+    /// Collectables that are rare on the board are rare in the recipes, too.
+    pub fn build_random<R: Rng + ?Sized>(
+        collectables: &[Collectable],
+        weights: &SpawnWeights,
+        difficulty: &Difficulty,
+        step: usize,
+        rng: &mut R,
+    ) -> Self {
+        let count = (difficulty.ingredients.at(step).max(1.) as usize).min(MAX_INGREDIENTS);
+        let rare_chance = difficulty.rare_chance.at(step).clamp(0., 1.) as f64;
+        let mut left: Vec<Collectable> = collectables.to_vec();
+        let mut ingredients = vec![];
+        let mut reward = 0.;
+        while ingredients.len() < count && !left.is_empty() {
+            let want_rare = rng.gen_bool(rare_chance);
+            let mut pool: Vec<Collectable> = left
+                .iter()
+                .filter(|collectable| difficulty.is_rare(collectable) == want_rare)
+                .cloned()
+                .collect();
+            // take any collectable once there is none of the wanted kind left
+            if pool.is_empty() {
+                pool = left.clone();
+            }
+            let collectable = weights.choose(&pool, rng).unwrap().clone();
+            left.retain(|other| other != &collectable);
+            let amount =
+                (rng.gen_range(4..8) as f32 * difficulty.amounts.at(step)).round() as usize;
+            reward += amount as f32
+                * difficulty.coins_per_ingredient
+                * if difficulty.is_rare(&collectable) {
+                    difficulty.rare_bonus
+                } else {
+                    1.
+                };
+            ingredients.push(Ingredients {
+                amount,
+                collectable,
+            });
+        }
+        Recipe {
+            name: "Wicked Potion".to_owned(),
+            ingredients,
+            reward: reward.round() as usize,
+            time_limit: None,
+        }
+    }
+
+    /// The recipe with its amounts multiplied by the factor and the reward growing along
+    fn scaled(&self, factor: f32) -> Self {
+        let ingredients: Vec<Ingredients> = self
+            .ingredients
+            .iter()
+            .map(|ingredient| Ingredients {
+                amount: (ingredient.amount as f32 * factor).round().max(1.) as usize,
+                collectable: ingredient.collectable.clone(),
+            })
+            .collect();
+        let total = |ingredients: &[Ingredients]| -> usize {
+            ingredients.iter().map(|ingredient| ingredient.amount).sum()
+        };
+        Recipe {
+            name: self.name.clone(),
+            reward: self.reward * total(&ingredients) / total(&self.ingredients).max(1),
+            ingredients,
+            time_limit: self.time_limit,
+        }
+    }
+
+    pub fn can_be_brewed_with(&self, collectables: &[Collectable]) -> bool {
+        self.ingredients
+            .iter()
+            .all(|ingredient| collectables.contains(&ingredient.collectable))
+    }
+
+    /// Can the recipe be ordered and shown on the scroll?
+    fn validate(&self) -> Result<(), String> {
+        if self.ingredients.is_empty() {
+            return Err(format!("Recipe '{}' has no ingredients", self.name));
+        }
+        if self.ingredients.len() > MAX_INGREDIENTS {
+            return Err(format!(
+                "Recipe '{}' has more than {} ingredients",
+                self.name, MAX_INGREDIENTS
+            ));
+        }
+        for (index, ingredient) in self.ingredients.iter().enumerate() {
+            if ingredient.amount == 0 {
+                return Err(format!(
+                    "Recipe '{}' needs no {:?}",
+                    self.name, ingredient.collectable
+                ));
+            }
+            if self.ingredients[..index]
+                .iter()
+                .any(|other| other.collectable == ingredient.collectable)
+            {
+                return Err(format!(
+                    "Recipe '{}' lists {:?} more than once",
+                    self.name, ingredient.collectable
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Ingredients {
+    pub amount: usize,
+    pub collectable: Collectable,
+}
+
+#[derive(Default)]
+struct RecipeBookLoader;
+
+impl AssetLoader for RecipeBookLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let book = ron::de::from_bytes::<RecipeBook>(bytes)?;
+            for recipe in book.recipes.iter() {
+                recipe.validate().map_err(anyhow::Error::msg)?;
+            }
+            load_context.set_default_asset(LoadedAsset::new(book));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["recipes"]
+    }
+}
@@ -0,0 +1,250 @@
+use crate::board::{Board, PickSlot, PlayerSwitch, RunClock};
+use crate::campaign::Campaign;
+use crate::items::ItemUsed;
+use crate::lost::{EndRun, RunOutcome};
+use crate::matcher::Slot;
+use crate::mode::{GameMode, RunSettings};
+use crate::random::GameRng;
+use crate::undo::TurnUndone;
+use crate::{storage, GameState, SystemLabels};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const REPLAY_KEY: &str = "replay";
+
+pub struct ReplayPlugin;
+
+/// This plugin records every run and plays recorded runs back
+/// A run is recorded as its seed and the switches of the player. The last run is saved as
+/// `replay.ron` next to the save file. A shared replay can be watched by starting the game with
+/// `--replay <file>`. Played back switches go through the same board systems as the player's.
+/// While a replay plays, the run's clock follows the recorded switches, so orders expire between
+/// the same switches as in the recorded run.
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Replays {
+            last: replay_from_arguments().or_else(|| storage::load(REPLAY_KEY)),
+            queued: None,
+            playing: None,
+        })
+        .insert_resource::<Recording>(None)
+        .add_system_set(
+            SystemSet::on_enter(GameState::Playing)
+                .with_system(start_recording.system().after(SystemLabels::StartRun)),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(record_switches.system().after(SystemLabels::UserInput))
+                .with_system(play_switches.system().label(SystemLabels::ReadInput)),
+        )
+        .add_system_set(SystemSet::on_enter(GameState::Lost).with_system(save_recording.system()));
+    }
+}
+
+/// A run that can be played again
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub run: ReplayedRun,
+    /// The switches of the player, the first switch first
+    pub switches: Vec<RecordedSwitch>,
+    /// Time of the run's clock when it ended
+    pub duration: f32,
+}
+
+impl Replay {
+    /// The rules of the recorded run, if it can still be played
+    pub fn settings(&self, campaign: Option<&Campaign>) -> Option<RunSettings> {
+        match self.run {
+            ReplayedRun::Mode(mode) => Some(mode.settings()),
+            ReplayedRun::Level(index) => campaign?
+                .levels
+                .get(index)
+                .map(|level| level.settings(index)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum ReplayedRun {
+    Mode(GameMode),
+    /// Index of the campaign level
+    Level(usize),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordedSwitch {
+    /// Time of the run's clock at the switch
+    pub time: f32,
+    pub one: Slot,
+    pub two: Slot,
+}
+
+pub struct Replays {
+    /// The last recorded or a shared run
+    pub last: Option<Replay>,
+    /// Played back in the next run
+    queued: Option<Replay>,
+    playing: Option<Playback>,
+}
+
+impl Replays {
+    /// Play the replay back in the next run
+    pub fn queue(&mut self, replay: Replay) {
+        self.queued = Some(replay);
+    }
+
+    /// The current or just finished run is a replay. Player input is ignored while it plays.
+    pub fn is_playing(&self) -> bool {
+        self.playing.is_some()
+    }
+}
+
+struct Playback {
+    replay: Replay,
+    /// Index of the next switch to play
+    next: usize,
+    /// The run's clock. It waits at the time of the next switch until the switch is played.
+    elapsed: f32,
+    /// The switch in progress. Its first slot was picked in the last frame.
+    second_pick: Option<Slot>,
+}
+
+/// The run that is recorded at the moment
+type Recording = Option<Replay>;
+
+fn start_recording(
+    mut replays: ResMut<Replays>,
+    mut recording: ResMut<Recording>,
+    settings: Res<RunSettings>,
+    rng: Res<GameRng>,
+) {
+    replays.playing = replays.queued.take().map(|replay| Playback {
+        replay,
+        next: 0,
+        elapsed: 0.,
+        second_pick: None,
+    });
+    if replays.is_playing() {
+        *recording = None;
+        return;
+    }
+    // record the rules of the run itself, the menu might show another mode by now
+    *recording = settings
+        .level
+        .map(ReplayedRun::Level)
+        .or_else(|| settings.mode.map(ReplayedRun::Mode))
+        .map(|run| Replay {
+            seed: rng.seed(),
+            run,
+            switches: vec![],
+            duration: 0.,
+        });
+}
+
+fn record_switches(
+    clock: Res<RunClock>,
+    mut recording: ResMut<Recording>,
+    mut switches: EventReader<PlayerSwitch>,
+    mut undone: EventReader<TurnUndone>,
+    mut items: EventReader<ItemUsed>,
+) {
+    let replay = match recording.as_mut() {
+        Some(replay) => replay,
+        None => return,
+    };
+    replay.duration = clock.seconds;
+    for switch in switches.iter() {
+        replay.switches.push(RecordedSwitch {
+            time: replay.duration,
+            one: switch.one.clone(),
+            two: switch.two.clone(),
+        });
+    }
+    if undone.iter().count() > 0 {
+        // the replay would play the switch that was taken back
+        info!("Stopped recording the run, because a switch was undone");
+        *recording = None;
+    } else if items.iter().count() > 0 {
+        // items are not recorded, so the board would be different when playing the replay
+        info!("Stopped recording the run, because an item was used");
+        *recording = None;
+    }
+}
+
+fn save_recording(mut recording: ResMut<Recording>, mut replays: ResMut<Replays>) {
+    if let Some(replay) = recording.take() {
+        storage::save(REPLAY_KEY, &replay);
+        replays.last = Some(replay);
+    }
+}
+
+/// Pick the slots of the recorded switches once their time has come and the board is at rest
+fn play_switches(
+    time: Res<Time>,
+    board: Res<Board>,
+    mut clock: ResMut<RunClock>,
+    mut replays: ResMut<Replays>,
+    mut picks: EventWriter<PickSlot>,
+    mut end_run: EventWriter<EndRun>,
+) {
+    let playback = match replays.playing.as_mut() {
+        Some(playback) => playback,
+        None => return,
+    };
+    if let Some(slot) = playback.second_pick.take() {
+        picks.send(PickSlot { slot });
+        return;
+    }
+    playback.elapsed += time.delta().as_secs_f32();
+    if let Some(switch) = playback.replay.switches.get(playback.next) {
+        playback.elapsed = playback.elapsed.min(switch.time);
+    }
+    clock.seconds = playback.elapsed;
+    if board.animating || !board.grid.is_settled() {
+        return;
+    }
+    match playback.replay.switches.get(playback.next) {
+        Some(switch) if playback.elapsed >= switch.time => {
+            picks.send(PickSlot {
+                slot: switch.one.clone(),
+            });
+            playback.second_pick = Some(switch.two.clone());
+            playback.next += 1;
+        }
+        Some(_) => {}
+        // the limits of the run end it by themselves, other runs were given up
+        None if playback.elapsed >= playback.replay.duration => end_run.send(EndRun {
+            outcome: RunOutcome::GaveUp,
+        }),
+        None => {}
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn replay_from_arguments() -> Option<Replay> {
+    None
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn replay_from_arguments() -> Option<Replay> {
+    let mut arguments = std::env::args().skip_while(|argument| argument != "--replay");
+    arguments.next()?;
+    let path = match arguments.next() {
+        Some(path) => path,
+        None => {
+            warn!("Expected a file after '--replay'");
+            return None;
+        }
+    };
+    let replay = std::fs::read_to_string(&path)
+        .map_err(|error| error.to_string())
+        .and_then(|serialized| ron::de::from_str(&serialized).map_err(|error| error.to_string()));
+    match replay {
+        Ok(replay) => Some(replay),
+        Err(error) => {
+            warn!("Failed to read the replay '{}': {}", path, error);
+            None
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use crate::board::{Cauldron, Score};
+use crate::campaign::Progress;
+use crate::mode::RunSettings;
+use crate::random::GameRng;
+use crate::replay::Replays;
+use crate::shop::{Inventory, Upgrades};
+use crate::{storage, GameState, SystemLabels};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const SAVE_KEY: &str = "save";
+/// Older versions kept the campaign progress under its own key
+const LEGACY_PROGRESS_KEY: &str = "campaign";
+/// High scores kept per mode
+const HIGH_SCORES: usize = 10;
+
+pub struct SavePlugin;
+
+/// This plugin keeps high scores, lifetime statistics and settings between sessions
+/// Every finished run is recorded when entering `GameState::Lost`
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(SaveData::load())
+            .insert_resource(LastRun::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Lost)
+                    .with_system(record_run.system().label(SystemLabels::RecordRun)),
+            );
+    }
+}
+
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SaveData {
+    /// The best runs of every mode, best first
+    pub high_scores: Vec<HighScore>,
+    pub runs_played: usize,
+    pub potions_brewed: usize,
+    pub coins_earned: usize,
+    /// Coins of finished runs that were not spent in the shop yet
+    pub coins: usize,
+    pub inventory: Inventory,
+    pub upgrades: Upgrades,
+    pub settings: Settings,
+    /// Stars earned in the levels of the campaign
+    pub campaign: Progress,
+}
+
+impl SaveData {
+    fn load() -> Self {
+        let mut save = storage::load::<SaveData>(SAVE_KEY).unwrap_or_default();
+        if save.adopt_legacy_progress(|| storage::load(LEGACY_PROGRESS_KEY)) {
+            save.store();
+        }
+        save
+    }
+
+    /// Fill an empty campaign with the progress that was saved before it moved into the save data
+    ///
+    /// The legacy progress is only loaded if it is needed. Returns true if it was taken over.
+    fn adopt_legacy_progress(&mut self, legacy: impl FnOnce() -> Option<Progress>) -> bool {
+        if !self.campaign.is_empty() {
+            return false;
+        }
+        match legacy() {
+            Some(progress) => {
+                self.campaign = progress;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The best coins of all recorded runs of the given mode
+    pub fn best_score(&self, mode: &str) -> Option<usize> {
+        self.high_scores
+            .iter()
+            .filter(|high_score| high_score.mode == mode)
+            .map(|high_score| high_score.coins)
+            .max()
+    }
+
+    pub fn store(&self) {
+        storage::save(SAVE_KEY, self);
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HighScore {
+    pub coins: usize,
+    pub potions: usize,
+    /// Name of the mode or campaign level of the run
+    pub mode: String,
+    pub seed: u64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Show a hint after some time without a switch
+    pub hints: bool,
+    /// Scales the volume of all channels
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub cooking_volume: f32,
+    pub effects_volume: f32,
+    pub muted: bool,
+}
+
+impl Settings {
+    /// The volume to play a channel with
+    pub fn volume(&self, channel_volume: f32) -> f32 {
+        if self.muted {
+            return 0.;
+        }
+        self.master_volume * channel_volume
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            hints: true,
+            master_volume: 1.0,
+            music_volume: 0.3,
+            cooking_volume: 1.0,
+            effects_volume: 0.4,
+            muted: false,
+        }
+    }
+}
+
+/// How the last run compares to the runs before it
+#[derive(Default)]
+pub struct LastRun {
+    /// The best score of the mode before the last run
+    pub previous_best: Option<usize>,
+    pub is_new_best: bool,
+}
+
+fn record_run(
+    mut save: ResMut<SaveData>,
+    mut last_run: ResMut<LastRun>,
+    score: Res<Score>,
+    cauldron: Res<Cauldron>,
+    settings: Res<RunSettings>,
+    rng: Res<GameRng>,
+    replays: Res<Replays>,
+) {
+    if replays.is_playing() {
+        // watching a run again does not count as playing it
+        *last_run = LastRun::default();
+        return;
+    }
+    let previous_best = save.best_score(&settings.name);
+    *last_run = LastRun {
+        previous_best,
+        is_new_best: score.money > previous_best.unwrap_or(0),
+    };
+
+    save.runs_played += 1;
+    save.potions_brewed += cauldron.finished_recipes;
+    save.coins_earned += score.money;
+    save.coins += score.money;
+    save.high_scores.push(HighScore {
+        coins: score.money,
+        potions: cauldron.finished_recipes,
+        mode: settings.name.clone(),
+        seed: rng.seed(),
+    });
+    save.high_scores
+        .sort_by(|one, other| other.coins.cmp(&one.coins));
+    let mut kept = HashMap::new();
+    save.high_scores.retain(|high_score| {
+        let count = kept.entry(high_score.mode.clone()).or_insert(0);
+        *count += 1;
+        *count <= HIGH_SCORES
+    });
+    save.store();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_progress_fills_an_empty_campaign() {
+        let mut legacy = Progress::default();
+        legacy.complete(0, 3);
+        legacy.complete(1, 1);
+        let mut save = SaveData::default();
+
+        assert!(save.adopt_legacy_progress(|| Some(legacy)));
+
+        assert_eq!(save.campaign.stars(0), 3);
+        assert_eq!(save.campaign.stars(1), 1);
+        assert!(save.campaign.is_unlocked(2));
+    }
+
+    #[test]
+    fn legacy_progress_does_not_replace_newer_progress() {
+        let mut legacy = Progress::default();
+        legacy.complete(0, 3);
+        legacy.complete(1, 2);
+        let mut save = SaveData::default();
+        save.campaign.complete(0, 1);
+
+        assert!(!save.adopt_legacy_progress(|| Some(legacy)));
+
+        assert_eq!(save.campaign.stars(0), 1);
+        assert!(!save.campaign.is_unlocked(2));
+    }
+}
@@ -0,0 +1,333 @@
+use crate::loading::FontAssets;
+use crate::menu::ButtonMaterials;
+use crate::save::{SaveData, Settings};
+use crate::GameState;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+
+const SLIDER_WIDTH: f32 = 240.;
+
+pub struct SettingsPlugin;
+
+/// This plugin adds the settings screen with the volume sliders and the mute toggle
+/// The screen is pushed on top of the menu or the pause menu and popped again with the back
+/// button or Escape. Pressing M anywhere mutes or unmutes all sounds.
+/// Changed settings are saved when the screen is left.
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<SettingsMaterials>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Settings).with_system(setup_settings.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Settings)
+                    .with_system(drag_slider.system())
+                    .with_system(click_settings_button.system())
+                    .with_system(show_settings.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Settings).with_system(remove_settings.system()),
+            )
+            .add_system(toggle_mute.system());
+    }
+}
+
+struct SettingsMaterials {
+    background: Handle<ColorMaterial>,
+    fill: Handle<ColorMaterial>,
+}
+
+impl FromWorld for SettingsMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        SettingsMaterials {
+            background: materials.add(Color::rgb(0.05, 0.05, 0.05).into()),
+            fill: materials.add(Color::rgb(0.45, 0.2, 0.55).into()),
+        }
+    }
+}
+
+/// The volumes that can be changed on the settings screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Volume {
+    Master,
+    Music,
+    Cooking,
+    Effects,
+}
+
+impl Volume {
+    const ALL: [Volume; 4] = [
+        Volume::Master,
+        Volume::Music,
+        Volume::Cooking,
+        Volume::Effects,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Volume::Master => "Volume",
+            Volume::Music => "Music",
+            Volume::Cooking => "Cooking",
+            Volume::Effects => "Effects",
+        }
+    }
+
+    fn get(&self, settings: &Settings) -> f32 {
+        match self {
+            Volume::Master => settings.master_volume,
+            Volume::Music => settings.music_volume,
+            Volume::Cooking => settings.cooking_volume,
+            Volume::Effects => settings.effects_volume,
+        }
+    }
+
+    fn get_mut<'a>(&self, settings: &'a mut Settings) -> &'a mut f32 {
+        match self {
+            Volume::Master => &mut settings.master_volume,
+            Volume::Music => &mut settings.music_volume,
+            Volume::Cooking => &mut settings.cooking_volume,
+            Volume::Effects => &mut settings.effects_volume,
+        }
+    }
+}
+
+struct SettingsUi;
+
+/// The part of a slider that shows its volume
+struct SliderFill(Volume);
+
+struct MuteButton;
+
+struct MuteText;
+
+struct BackButton;
+
+fn setup_settings(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    materials: Res<SettingsMaterials>,
+    button_materials: Res<ButtonMaterials>,
+    save: Res<SaveData>,
+) {
+    let text = |value: String, font_size: f32| Text {
+        sections: vec![TextSection {
+            value,
+            style: TextStyle {
+                font: font_assets.fira_sans.clone(),
+                font_size,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+        }],
+        alignment: Default::default(),
+    };
+    let absolute = |left: f32, top: f32, size: Size<Val>| Style {
+        size,
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        position_type: PositionType::Absolute,
+        position: Rect {
+            left: Val::Px(left),
+            top: Val::Px(top),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    // covers whatever the screen was opened on
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            material: materials.background.clone(),
+            ..Default::default()
+        })
+        .insert(SettingsUi)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: absolute(320., 30., Size::new(Val::Auto, Val::Auto)),
+                text: text("Settings".to_owned(), 40.),
+                ..Default::default()
+            });
+            for (index, volume) in Volume::ALL.iter().enumerate() {
+                let top = 110. + index as f32 * 60.;
+                parent.spawn_bundle(TextBundle {
+                    style: absolute(200., top, Size::new(Val::Auto, Val::Auto)),
+                    text: text(volume.label().to_owned(), 30.),
+                    ..Default::default()
+                });
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            justify_content: JustifyContent::FlexStart,
+                            ..absolute(
+                                360.,
+                                top + 6.,
+                                Size::new(Val::Px(SLIDER_WIDTH), Val::Px(24.)),
+                            )
+                        },
+                        material: button_materials.normal.clone(),
+                        ..Default::default()
+                    })
+                    .insert(*volume)
+                    .with_children(|slider| {
+                        slider
+                            .spawn_bundle(NodeBundle {
+                                style: Style {
+                                    size: Size::new(
+                                        Val::Percent(volume.get(&save.settings) * 100.),
+                                        Val::Percent(100.),
+                                    ),
+                                    ..Default::default()
+                                },
+                                material: materials.fill.clone(),
+                                ..Default::default()
+                            })
+                            // the slider below is the one that is dragged
+                            .insert(FocusPolicy::Pass)
+                            .insert(SliderFill(*volume));
+                    });
+            }
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: absolute(300., 360., Size::new(Val::Px(200.), Val::Px(50.))),
+                    material: button_materials.normal.clone(),
+                    ..Default::default()
+                })
+                .insert(MuteButton)
+                .with_children(|button| {
+                    button
+                        .spawn_bundle(TextBundle {
+                            text: text(mute_label(&save.settings), 30.),
+                            ..Default::default()
+                        })
+                        .insert(MuteText);
+                });
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: absolute(300., 430., Size::new(Val::Px(200.), Val::Px(50.))),
+                    material: button_materials.normal.clone(),
+                    ..Default::default()
+                })
+                .insert(BackButton)
+                .with_children(|button| {
+                    button.spawn_bundle(TextBundle {
+                        text: text("Back".to_owned(), 30.),
+                        ..Default::default()
+                    });
+                });
+        });
+}
+
+fn mute_label(settings: &Settings) -> String {
+    if settings.muted {
+        "Sound: off".to_owned()
+    } else {
+        "Sound: on".to_owned()
+    }
+}
+
+/// Set the volume of a slider to where it is pressed, for as long as it is held
+fn drag_slider(
+    windows: Res<Windows>,
+    sliders: Query<(&Interaction, &Volume, &Node, &GlobalTransform)>,
+    mut save: ResMut<SaveData>,
+) {
+    let cursor = match windows
+        .get_primary()
+        .and_then(|window| window.cursor_position())
+    {
+        Some(cursor) => cursor,
+        None => return,
+    };
+    for (interaction, volume, node, transform) in sliders.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+        // UI nodes are placed by their center, in window coordinates
+        let left = transform.translation.x - node.size.x / 2.;
+        let value = ((cursor.x - left) / node.size.x).clamp(0., 1.);
+        if (volume.get(&save.settings) - value).abs() > f32::EPSILON {
+            *volume.get_mut(&mut save.settings) = value;
+        }
+    }
+}
+
+fn click_settings_button(
+    button_materials: Res<ButtonMaterials>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+    mut save: ResMut<SaveData>,
+    mut interaction_query: Query<
+        (
+            &Interaction,
+            &mut Handle<ColorMaterial>,
+            Option<&MuteButton>,
+            Option<&BackButton>,
+        ),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        // the screen below should not see the same key press
+        keys.reset(KeyCode::Escape);
+        state.pop().unwrap();
+        return;
+    }
+    for (interaction, mut material, mute_button, back_button) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked if mute_button.is_some() => {
+                save.settings.muted = !save.settings.muted;
+            }
+            Interaction::Clicked if back_button.is_some() => {
+                state.pop().unwrap();
+                return;
+            }
+            // the sliders keep their color, they are dragged instead
+            Interaction::Clicked => {}
+            Interaction::Hovered => {
+                *material = button_materials.hovered.clone();
+            }
+            Interaction::None => {
+                *material = button_materials.normal.clone();
+            }
+        }
+    }
+}
+
+fn show_settings(
+    save: Res<SaveData>,
+    mut fills: Query<(&SliderFill, &mut Style)>,
+    mut mute_text: Query<&mut Text, With<MuteText>>,
+) {
+    if !save.is_changed() {
+        return;
+    }
+    for (fill, mut style) in fills.iter_mut() {
+        style.size.width = Val::Percent(fill.0.get(&save.settings) * 100.);
+    }
+    if let Ok(mut text) = mute_text.single_mut() {
+        text.sections[0].value = mute_label(&save.settings);
+    }
+}
+
+fn toggle_mute(keys: Res<Input<KeyCode>>, mut save: ResMut<SaveData>) {
+    if keys.just_pressed(KeyCode::M) {
+        save.settings.muted = !save.settings.muted;
+        save.store();
+    }
+}
+
+fn remove_settings(
+    mut commands: Commands,
+    elements: Query<Entity, With<SettingsUi>>,
+    save: Res<SaveData>,
+) {
+    for entity in elements.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    save.store();
+}
@@ -0,0 +1,383 @@
+use crate::loading::FontAssets;
+use crate::menu::ButtonMaterials;
+use crate::save::SaveData;
+use crate::GameState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Highest level of every upgrade
+const MAX_UPGRADE_LEVEL: usize = 5;
+
+pub struct ShopPlugin;
+
+/// This plugin adds the shop screen to the menu
+/// The coins of finished runs buy items that are used up during runs and upgrades that last.
+/// Both are kept in the save data.
+impl Plugin for ShopPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(SystemSet::on_enter(GameState::Shop).with_system(setup_shop.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Shop)
+                    .with_system(click_shop_button.system())
+                    .with_system(show_offers.system()),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Shop).with_system(remove_shop.system()));
+    }
+}
+
+/// Consumables that help out once during a run
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Item {
+    /// Rearrange the board
+    Shuffle,
+    /// Remove one tile or obstacle
+    Hammer,
+    /// Replace the oldest order
+    Reroll,
+    /// More switches in runs with a move limit
+    Moves,
+}
+
+impl Item {
+    pub const ALL: [Item; 4] = [Item::Shuffle, Item::Hammer, Item::Reroll, Item::Moves];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Item::Shuffle => "Shuffle",
+            Item::Hammer => "Hammer",
+            Item::Reroll => "New recipe",
+            Item::Moves => "+5 Moves",
+        }
+    }
+
+    fn price(&self) -> usize {
+        match self {
+            Item::Shuffle => 40,
+            Item::Hammer => 60,
+            Item::Reroll => 50,
+            Item::Moves => 80,
+        }
+    }
+}
+
+/// The items the player owns
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Inventory {
+    items: HashMap<Item, usize>,
+}
+
+impl Inventory {
+    pub fn count(&self, item: Item) -> usize {
+        *self.items.get(&item).unwrap_or(&0)
+    }
+
+    pub fn add(&mut self, item: Item) {
+        *self.items.entry(item).or_insert(0) += 1;
+    }
+
+    /// Use up one of the item. Returns false if there is none left.
+    pub fn take(&mut self, item: Item) -> bool {
+        match self.items.get_mut(&item) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Upgrade {
+    /// Every potion sells for more coins
+    Reward,
+    /// The shelf holds more ingredients for the orders to come
+    Shelf,
+}
+
+impl Upgrade {
+    const ALL: [Upgrade; 2] = [Upgrade::Reward, Upgrade::Shelf];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Upgrade::Reward => "Better prices",
+            Upgrade::Shelf => "Bigger shelf",
+        }
+    }
+
+    /// Every level costs more than the one before
+    fn price(&self, level: usize) -> usize {
+        match self {
+            Upgrade::Reward => 150 * (level + 1),
+            Upgrade::Shelf => 120 * (level + 1),
+        }
+    }
+}
+
+/// The levels of the bought upgrades
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Upgrades {
+    pub reward: usize,
+    #[serde(alias = "cauldron")]
+    pub shelf: usize,
+}
+
+impl Upgrades {
+    pub fn level(&self, upgrade: Upgrade) -> usize {
+        match upgrade {
+            Upgrade::Reward => self.reward,
+            Upgrade::Shelf => self.shelf,
+        }
+    }
+
+    fn level_mut(&mut self, upgrade: Upgrade) -> &mut usize {
+        match upgrade {
+            Upgrade::Reward => &mut self.reward,
+            Upgrade::Shelf => &mut self.shelf,
+        }
+    }
+
+    /// Percent added to the reward of every potion
+    pub fn reward_bonus(&self) -> usize {
+        self.reward * 10
+    }
+
+    /// Room on the shelf in addition to its base capacity
+    pub fn shelf_space(&self) -> usize {
+        self.shelf * 2
+    }
+}
+
+/// Something that can be bought in the shop
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Offer {
+    Item(Item),
+    Upgrade(Upgrade),
+}
+
+impl Offer {
+    fn label(&self) -> &'static str {
+        match self {
+            Offer::Item(item) => item.label(),
+            Offer::Upgrade(upgrade) => upgrade.label(),
+        }
+    }
+
+    /// `None` once an upgrade reached its highest level
+    fn price(&self, save: &SaveData) -> Option<usize> {
+        match self {
+            Offer::Item(item) => Some(item.price()),
+            Offer::Upgrade(upgrade) => {
+                let level = save.upgrades.level(*upgrade);
+                if level >= MAX_UPGRADE_LEVEL {
+                    return None;
+                }
+                Some(upgrade.price(level))
+            }
+        }
+    }
+
+    fn owned(&self, save: &SaveData) -> String {
+        match self {
+            Offer::Item(item) => format!("Owned: {}", save.inventory.count(*item)),
+            Offer::Upgrade(upgrade) => format!(
+                "Level {}/{}",
+                save.upgrades.level(*upgrade),
+                MAX_UPGRADE_LEVEL
+            ),
+        }
+    }
+
+    fn price_label(&self, save: &SaveData) -> String {
+        match self.price(save) {
+            Some(price) => format!("{} coins", price),
+            None => "Sold out".to_owned(),
+        }
+    }
+
+    /// Pay for the offer. Returns false if the coins are not enough.
+    fn buy(&self, save: &mut SaveData) -> bool {
+        let price = match self.price(save) {
+            Some(price) if price <= save.coins => price,
+            _ => return false,
+        };
+        save.coins -= price;
+        match self {
+            Offer::Item(item) => save.inventory.add(*item),
+            Offer::Upgrade(upgrade) => *save.upgrades.level_mut(*upgrade) += 1,
+        }
+        true
+    }
+}
+
+struct ShopUi;
+
+struct CoinsText;
+
+/// Shows how much of the offer the player owns
+struct OwnedText(Offer);
+
+struct PriceText(Offer);
+
+struct BackButton;
+
+fn setup_shop(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    button_materials: Res<ButtonMaterials>,
+    save: Res<SaveData>,
+) {
+    let text = |value: String, font_size: f32| Text {
+        sections: vec![TextSection {
+            value,
+            style: TextStyle {
+                font: font_assets.fira_sans.clone(),
+                font_size,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+        }],
+        alignment: Default::default(),
+    };
+    let absolute = |left: f32, top: f32, size: Size<Val>| Style {
+        size,
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        position_type: PositionType::Absolute,
+        position: Rect {
+            left: Val::Px(left),
+            top: Val::Px(top),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: absolute(60., 30., Size::new(Val::Auto, Val::Auto)),
+            text: text(format!("Coins: {}", save.coins), 40.),
+            ..Default::default()
+        })
+        .insert(CoinsText)
+        .insert(ShopUi);
+    let offers = Item::ALL
+        .iter()
+        .map(|item| Offer::Item(*item))
+        .chain(Upgrade::ALL.iter().map(|upgrade| Offer::Upgrade(*upgrade)));
+    for (index, offer) in offers.enumerate() {
+        let top = 100. + index as f32 * 64.;
+        commands
+            .spawn_bundle(TextBundle {
+                style: absolute(60., top + 8., Size::new(Val::Auto, Val::Auto)),
+                text: text(offer.label().to_owned(), 30.),
+                ..Default::default()
+            })
+            .insert(ShopUi);
+        commands
+            .spawn_bundle(TextBundle {
+                style: absolute(330., top + 12., Size::new(Val::Auto, Val::Auto)),
+                text: text(offer.owned(&save), 24.),
+                ..Default::default()
+            })
+            .insert(OwnedText(offer))
+            .insert(ShopUi);
+        commands
+            .spawn_bundle(ButtonBundle {
+                style: absolute(540., top, Size::new(Val::Px(180.), Val::Px(50.))),
+                material: button_materials.normal.clone(),
+                ..Default::default()
+            })
+            .insert(offer)
+            .insert(ShopUi)
+            .with_children(|parent| {
+                parent
+                    .spawn_bundle(TextBundle {
+                        text: text(offer.price_label(&save), 26.),
+                        ..Default::default()
+                    })
+                    .insert(PriceText(offer))
+                    .insert(ShopUi);
+            });
+    }
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: absolute(540., 500., Size::new(Val::Px(180.), Val::Px(50.))),
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .insert(BackButton)
+        .insert(ShopUi)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: text("Back".to_owned(), 30.),
+                    ..Default::default()
+                })
+                .insert(ShopUi);
+        });
+}
+
+fn click_shop_button(
+    button_materials: Res<ButtonMaterials>,
+    mut state: ResMut<State<GameState>>,
+    mut save: ResMut<SaveData>,
+    mut interaction_query: Query<
+        (
+            &Interaction,
+            &mut Handle<ColorMaterial>,
+            Option<&Offer>,
+            Option<&BackButton>,
+        ),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut material, offer, back_button) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                if let Some(offer) = offer {
+                    if offer.buy(&mut save) {
+                        save.store();
+                    }
+                } else if back_button.is_some() {
+                    state.set(GameState::Menu).unwrap();
+                    return;
+                }
+            }
+            Interaction::Hovered => {
+                *material = button_materials.hovered.clone();
+            }
+            Interaction::None => {
+                *material = button_materials.normal.clone();
+            }
+        }
+    }
+}
+
+fn show_offers(
+    save: Res<SaveData>,
+    mut texts: QuerySet<(
+        Query<&mut Text, With<CoinsText>>,
+        Query<(&mut Text, &OwnedText)>,
+        Query<(&mut Text, &PriceText)>,
+    )>,
+) {
+    if !save.is_changed() {
+        return;
+    }
+    if let Ok(mut text) = texts.q0_mut().single_mut() {
+        text.sections[0].value = format!("Coins: {}", save.coins);
+    }
+    for (mut text, owned) in texts.q1_mut().iter_mut() {
+        text.sections[0].value = owned.0.owned(&save);
+    }
+    for (mut text, price) in texts.q2_mut().iter_mut() {
+        text.sections[0].value = price.0.price_label(&save);
+    }
+}
+
+fn remove_shop(mut commands: Commands, elements: Query<Entity, With<ShopUi>>) {
+    for entity in elements.iter() {
+        commands.entity(entity).despawn();
+    }
+}
@@ -0,0 +1,1568 @@
+//! The match-3 rules without any dependency on the ECS
+//!
+//! A [Grid] only knows about [Tile]s. Every mutating method returns the [BoardEvent]s
+//! it caused, so the Bevy systems in `board.rs` can render them, while bots and tests can drive
+//! the game without spawning a single sprite.
+
+use crate::matcher::{Collectable, Obstacle, Pattern, Slot, SpawnWeights, Special, Tile};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BoardEvent {
+    /// The content of both slots was exchanged
+    Switched { one: Slot, two: Slot },
+    /// The pattern was found on the board. Its slots are removed right after.
+    Matched { pattern: Pattern },
+    /// A pattern left a special tile behind. The tile stays in the slot instead of being removed.
+    SpecialCreated { slot: Slot, special: Special },
+    /// The special tile in the slot was removed and clears more slots
+    Activated { slot: Slot, special: Special },
+    /// The collectable in the slot was part of a pattern and left the board
+    Removed {
+        slot: Slot,
+        collectable: Collectable,
+    },
+    /// The collectable fell down to fill up a gap
+    Dropped { from: Slot, to: Slot },
+    /// A new collectable was created in the slot. It should appear `drop_height` slots above it.
+    Spawned {
+        slot: Slot,
+        collectable: Collectable,
+        drop_height: usize,
+    },
+    /// The tiles were rearranged. Every tile moved from the first to the second slot of a pair.
+    Shuffled { moves: Vec<(Slot, Slot)> },
+    /// The obstacle in the slot was broken
+    ObstacleCleared { slot: Slot, obstacle: Obstacle },
+}
+
+/// How often [Grid::shuffle] rearranges the tiles before giving up
+const SHUFFLE_ATTEMPTS: usize = 100;
+/// How often [Grid::new] draws new collectables for slots that are part of a pattern
+const REROLL_ATTEMPTS: usize = 100;
+
+/// The shape of a grid
+///
+/// Slots that are not open are holes. They never hold a tile and collectables fall past them.
+/// In data files a layout is a list of rows from top to bottom with `o` for a slot and `.` for a hole.
+/// Slots can start with an obstacle: `i` for ice, `c` for a chain and `#` for a stone.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(try_from = "Vec<String>")]
+pub struct Layout {
+    width: usize,
+    height: usize,
+    /// Column major like the slots of a [Grid]
+    open: Vec<Vec<bool>>,
+    /// The obstacles a grid with this layout starts with
+    obstacles: Vec<Vec<Option<Obstacle>>>,
+}
+
+impl Layout {
+    pub fn rectangle(width: usize, height: usize) -> Self {
+        Layout {
+            width,
+            height,
+            open: vec![vec![true; height]; width],
+            obstacles: vec![vec![None; height]; width],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn is_open(&self, slot: &Slot) -> bool {
+        self.open
+            .get(slot.column)
+            .and_then(|column| column.get(slot.row))
+            .copied()
+            .unwrap_or(false)
+    }
+
+    pub fn obstacle(&self, slot: &Slot) -> Option<Obstacle> {
+        self.obstacles
+            .get(slot.column)
+            .and_then(|column| column.get(slot.row))
+            .copied()
+            .flatten()
+    }
+
+    /// The open rows of the column from bottom to top
+    fn open_rows(&self, column: usize) -> Vec<usize> {
+        (0..self.height)
+            .filter(|&row| self.open[column][row])
+            .collect()
+    }
+
+    pub fn open_slots(&self) -> Vec<Slot> {
+        (0..self.width)
+            .flat_map(|column| {
+                self.open_rows(column)
+                    .into_iter()
+                    .map(move |row| Slot { row, column })
+            })
+            .collect()
+    }
+}
+
+impl TryFrom<Vec<String>> for Layout {
+    type Error = String;
+
+    fn try_from(rows: Vec<String>) -> Result<Self, Self::Error> {
+        let height = rows.len();
+        let width = rows.first().map(|row| row.chars().count()).unwrap_or(0);
+        let mut open = vec![vec![false; height]; width];
+        let mut obstacles = vec![vec![None; height]; width];
+        // the first row is the top of the grid
+        for (row, line) in rows.iter().rev().enumerate() {
+            if line.chars().count() != width {
+                return Err("All rows of a layout need to have the same length".to_owned());
+            }
+            for (column, character) in line.chars().enumerate() {
+                let (is_open, obstacle) = match character {
+                    'o' => (true, None),
+                    '.' => (false, None),
+                    'i' => (true, Some(Obstacle::Ice)),
+                    'c' => (true, Some(Obstacle::Chain)),
+                    '#' => (true, Some(Obstacle::Stone)),
+                    other => return Err(format!("Unknown slot '{}' in layout", other)),
+                };
+                open[column][row] = is_open;
+                obstacles[column][row] = obstacle;
+            }
+        }
+        if !open.iter().flatten().any(|&open| open) {
+            return Err("A layout needs at least one open slot".to_owned());
+        }
+
+        Ok(Layout {
+            width,
+            height,
+            open,
+            obstacles,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Grid {
+    height: usize,
+    width: usize,
+    layout: Layout,
+    /// Holes and stones keep a tile as well. It is never matched, moved or returned.
+    slots: Vec<Vec<Tile>>,
+    /// The obstacles that are not broken yet
+    obstacles: Vec<Vec<Option<Obstacle>>>,
+    /// The collectables that new tiles are drawn from
+    collectables: Vec<Collectable>,
+    weights: SpawnWeights,
+    /// The slots of the last switch. Special tiles are created here if possible.
+    switched: Vec<Slot>,
+    /// Colour wipes that were switched and the collectable they should clear
+    triggered: Vec<(Slot, Collectable)>,
+    /// The number of waves taken since the last switch
+    cascade: usize,
+}
+
+impl Grid {
+    /// Fill the open slots of the layout with random collectables out of the given ones, drawn by
+    /// their weights
+    ///
+    /// Obstacles should only be broken by the player's switches, so collectables are drawn again
+    /// until the grid starts without patterns. The returned events spawn every collectable of the
+    /// new grid.
+    pub fn new<R: Rng + ?Sized>(
+        layout: Layout,
+        collectables: Vec<Collectable>,
+        weights: SpawnWeights,
+        rng: &mut R,
+    ) -> (Self, Vec<BoardEvent>) {
+        assert!(
+            !collectables.is_empty(),
+            "A grid needs collectables to fill its slots with"
+        );
+        let empty = layout
+            .open_slots()
+            .into_iter()
+            .filter(|slot| layout.obstacle(slot) != Some(Obstacle::Stone))
+            .collect();
+        let mut grid = Grid {
+            height: layout.height(),
+            width: layout.width(),
+            slots: vec![vec![Tile::from(collectables[0].clone()); layout.height()]; layout.width()],
+            obstacles: layout.obstacles.clone(),
+            layout,
+            collectables,
+            weights,
+            switched: vec![],
+            triggered: vec![],
+            cascade: 0,
+        };
+        let mut events = grid.refill(empty, rng);
+        for _attempt in 0..REROLL_ATTEMPTS {
+            let patterns = grid.find_patterns();
+            if patterns.is_empty() {
+                break;
+            }
+            for slot in patterns.iter().flat_map(|pattern| pattern.slots()) {
+                grid.slots[slot.column][slot.row] = grid.random_collectable(rng).into();
+            }
+        }
+        for event in events.iter_mut() {
+            if let BoardEvent::Spawned {
+                slot, collectable, ..
+            } = event
+            {
+                *collectable = grid.get(slot).collectable.clone();
+            }
+        }
+
+        (grid, events)
+    }
+
+    /// Create a grid from the given columns. All columns need to have the same length.
+    pub fn from_columns(columns: Vec<Vec<Collectable>>) -> Self {
+        let slots: Vec<Vec<Tile>> = columns
+            .into_iter()
+            .map(|column| column.into_iter().map(Tile::from).collect())
+            .collect();
+        let height = slots.first().map(|column| column.len()).unwrap_or(0);
+        assert!(
+            slots.iter().all(|column| column.len() == height),
+            "All columns of a grid need to have the same height"
+        );
+        Grid {
+            height,
+            width: slots.len(),
+            layout: Layout::rectangle(slots.len(), height),
+            obstacles: vec![vec![None; height]; slots.len()],
+            slots,
+            collectables: Collectable::all(),
+            weights: SpawnWeights::default(),
+            switched: vec![],
+            triggered: vec![],
+            cascade: 0,
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    pub fn get(&self, slot: &Slot) -> &Tile {
+        self.slots.get(slot.column).unwrap().get(slot.row).unwrap()
+    }
+
+    pub fn obstacle(&self, slot: &Slot) -> Option<Obstacle> {
+        self.obstacles
+            .get(slot.column)
+            .and_then(|column| column.get(slot.row))
+            .copied()
+            .flatten()
+    }
+
+    /// All obstacles that are not broken yet
+    pub fn obstacles(&self) -> Vec<(Slot, Obstacle)> {
+        self.layout
+            .open_slots()
+            .into_iter()
+            .filter_map(|slot| self.obstacle(&slot).map(|obstacle| (slot, obstacle)))
+            .collect()
+    }
+
+    /// Does the slot hold a tile? Holes and stones don't.
+    pub fn has_tile(&self, slot: &Slot) -> bool {
+        self.layout.is_open(slot) && self.obstacle(slot) != Some(Obstacle::Stone)
+    }
+
+    /// Can the tile in the slot be switched and fall down?
+    fn is_movable(&self, slot: &Slot) -> bool {
+        self.has_tile(slot) && self.obstacle(slot) != Some(Obstacle::Chain)
+    }
+
+    fn random_collectable<R: Rng + ?Sized>(&self, rng: &mut R) -> Collectable {
+        self.weights
+            .choose(&self.collectables, rng)
+            .expect("A grid always has collectables")
+            .clone()
+    }
+
+    pub fn find_patterns(&self) -> Vec<Pattern> {
+        Pattern::combine(
+            self.find_patterns_in_columns(),
+            self.find_patterns_in_rows(),
+        )
+    }
+
+    pub fn switch(&mut self, one: &Slot, two: &Slot) -> BoardEvent {
+        self.switch_in_slots(one, two);
+        for (slot, other) in [(one, two), (two, one)].iter() {
+            if self.get(slot).special == Some(Special::ColourWipe) {
+                let collectable = self.get(other).collectable.clone();
+                self.triggered.push(((*slot).clone(), collectable));
+            }
+        }
+        self.switched = vec![one.clone(), two.clone()];
+        self.cascade = 0;
+
+        BoardEvent::Switched {
+            one: one.clone(),
+            two: two.clone(),
+        }
+    }
+
+    fn switch_in_slots(&mut self, one: &Slot, two: &Slot) {
+        let tile_one = self.get(one).clone();
+        let tile_two = self.get(two).clone();
+        self.slots[one.column][one.row] = tile_two;
+        self.slots[two.column][two.row] = tile_one;
+    }
+
+    pub fn has_pattern_after_switch(&mut self, one: &Slot, two: &Slot) -> bool {
+        if !self.is_movable(one) || !self.is_movable(two) {
+            return false;
+        }
+        if self.get(one).special == Some(Special::ColourWipe)
+            || self.get(two).special == Some(Special::ColourWipe)
+        {
+            return true;
+        }
+        self.switch_in_slots(one, two);
+        let has_patterns = !self.find_patterns().is_empty();
+        self.switch_in_slots(one, two);
+
+        has_patterns
+    }
+
+    /// Is there any switch of two neighboring slots that would create a pattern?
+    pub fn has_possible_switch(&mut self) -> bool {
+        for (one, two) in self.switches() {
+            if self.has_pattern_after_switch(&one, &two) {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Every pair of neighboring tiles that can be switched
+    fn switches(&self) -> Vec<(Slot, Slot)> {
+        let mut switches = vec![];
+        for current in self.layout.open_slots() {
+            if !self.is_movable(&current) {
+                continue;
+            }
+            for other in [current.walk(0, 1), current.walk(1, 0)].iter() {
+                if self.is_movable(other) {
+                    switches.push((current.clone(), other.clone()));
+                }
+            }
+        }
+
+        switches
+    }
+
+    /// The switch that clears the most tiles with its first wave
+    ///
+    /// Tiles of the preferred collectables count double and patterns leaving a special tile behind
+    /// are worth a bonus. Returns `None` if there is no possible switch.
+    pub fn best_switch(&mut self, preferred: &[Collectable]) -> Option<(Slot, Slot)> {
+        let mut best: Option<((Slot, Slot), usize)> = None;
+        for (one, two) in self.switches() {
+            let value = self.switch_value(&one, &two, preferred);
+            if value > best.as_ref().map_or(0, |(_, best_value)| *best_value) {
+                best = Some(((one, two), value));
+            }
+        }
+
+        best.map(|(switch, _)| switch)
+    }
+
+    fn switch_value(&mut self, one: &Slot, two: &Slot, preferred: &[Collectable]) -> usize {
+        let tile_value = |tile: &Tile| {
+            if preferred.contains(&tile.collectable) {
+                2
+            } else {
+                1
+            }
+        };
+        for (wipe, other) in [(one, two), (two, one)].iter() {
+            if self.get(wipe).special == Some(Special::ColourWipe) {
+                let collectable = self.get(other).collectable.clone();
+                return self
+                    .special_area(wipe, Special::ColourWipe, &collectable)
+                    .iter()
+                    .map(|slot| tile_value(self.get(slot)))
+                    .sum();
+            }
+        }
+        self.switch_in_slots(one, two);
+        let value = self
+            .find_patterns()
+            .iter()
+            .map(|pattern| {
+                let bonus = if pattern.special().is_some() { 3 } else { 0 };
+                pattern
+                    .slots()
+                    .iter()
+                    .map(|slot| tile_value(self.get(slot)))
+                    .sum::<usize>()
+                    + bonus
+            })
+            .sum();
+        self.switch_in_slots(one, two);
+
+        value
+    }
+
+    /// The number of waves taken since the last switch
+    ///
+    /// The first wave is the direct result of the switch, every further wave is a chain reaction.
+    pub fn cascade(&self) -> usize {
+        self.cascade
+    }
+
+    /// Are there no patterns or switched colour wipes left to take?
+    pub fn is_settled(&self) -> bool {
+        self.triggered.is_empty() && self.find_patterns().is_empty()
+    }
+
+    /// Remove all slots that are part of a pattern or cleared by special tiles, let the remaining
+    /// collectables fall down and refill the grid
+    ///
+    /// This is one wave of a cascade. It returns no events if the grid is settled.
+    pub fn take_patterns<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Vec<BoardEvent> {
+        let patterns = self.find_patterns();
+        let switched = std::mem::take(&mut self.switched);
+        let triggered = std::mem::take(&mut self.triggered);
+        if patterns.is_empty() && triggered.is_empty() {
+            return vec![];
+        }
+        self.cascade += 1;
+
+        let mut events = vec![];
+        let mut slots = vec![];
+        let mut created: Vec<(Slot, Special)> = vec![];
+        for pattern in patterns {
+            if let Some(special) = pattern.special() {
+                let pattern_slots = pattern.slots();
+                let slot = pattern_slots
+                    .iter()
+                    .find(|slot| switched.contains(slot))
+                    .unwrap_or(&pattern_slots[pattern_slots.len() / 2])
+                    .clone();
+                // an existing special tile in this slot gets activated instead
+                if self.get(&slot).special.is_none()
+                    && !created.iter().any(|(other, _)| other == &slot)
+                {
+                    created.push((slot, special));
+                }
+            }
+            slots.extend(pattern.slots().iter().cloned());
+            events.push(BoardEvent::Matched { pattern });
+        }
+        let matched = slots.clone();
+        for (slot, special) in created.iter() {
+            events.push(BoardEvent::SpecialCreated {
+                slot: slot.clone(),
+                special: *special,
+            });
+        }
+        let keep: Vec<Slot> = created.iter().map(|(slot, _)| slot.clone()).collect();
+        slots.retain(|slot| !keep.contains(slot));
+
+        let mut activated = vec![];
+        for (slot, collectable) in triggered {
+            events.push(BoardEvent::Activated {
+                slot: slot.clone(),
+                special: Special::ColourWipe,
+            });
+            slots.push(slot.clone());
+            slots.append(&mut self.special_area(&slot, Special::ColourWipe, &collectable));
+            activated.push(slot);
+        }
+        self.activate_specials(&mut slots, &keep, activated, &mut events);
+        let crumbled = self.break_obstacles(&mut slots, &matched, &mut events);
+
+        for (slot, special) in created {
+            self.slots[slot.column][slot.row].special = Some(special);
+        }
+        events.append(&mut self.collapse(slots, crumbled, rng));
+
+        events
+    }
+
+    /// Add all slots cleared by special tiles that are about to be removed
+    ///
+    /// Special tiles hit by other special tiles activate as well. Chained special tiles only lose
+    /// their chain and stay on the grid, so they don't go off yet.
+    fn activate_specials(
+        &self,
+        slots: &mut Vec<Slot>,
+        keep: &[Slot],
+        mut activated: Vec<Slot>,
+        events: &mut Vec<BoardEvent>,
+    ) {
+        let mut queue = slots.clone();
+        while let Some(slot) = queue.pop() {
+            if activated.contains(&slot) || self.obstacle(&slot) == Some(Obstacle::Chain) {
+                continue;
+            }
+            let tile = self.get(&slot);
+            if let Some(special) = tile.special {
+                events.push(BoardEvent::Activated {
+                    slot: slot.clone(),
+                    special,
+                });
+                for target in self.special_area(&slot, special, &tile.collectable) {
+                    if !slots.contains(&target) && !keep.contains(&target) {
+                        slots.push(target.clone());
+                        queue.push(target);
+                    }
+                }
+                activated.push(slot);
+            }
+        }
+    }
+
+    /// Break the obstacles hit by this wave
+    ///
+    /// Chained tiles lose their chain instead of being removed and ice breaks together with its
+    /// tile. Stones next to a matched slot crumble. Returns the slots of the crumbled stones.
+    fn break_obstacles(
+        &mut self,
+        slots: &mut Vec<Slot>,
+        matched: &[Slot],
+        events: &mut Vec<BoardEvent>,
+    ) -> Vec<Slot> {
+        slots.sort();
+        slots.dedup();
+        let mut broken = vec![];
+        for slot in slots.iter() {
+            match self.obstacle(slot) {
+                Some(Obstacle::Stone) | None => {}
+                Some(obstacle) => broken.push((slot.clone(), obstacle)),
+            }
+        }
+        let mut crumbled = vec![];
+        for (stone, obstacle) in self.obstacles() {
+            if obstacle == Obstacle::Stone
+                && self
+                    .neighbors(&stone)
+                    .iter()
+                    .any(|neighbor| matched.contains(neighbor))
+            {
+                crumbled.push(stone.clone());
+                broken.push((stone, obstacle));
+            }
+        }
+        for (slot, obstacle) in broken {
+            if obstacle == Obstacle::Chain {
+                slots.retain(|other| other != &slot);
+            }
+            self.obstacles[slot.column][slot.row] = None;
+            events.push(BoardEvent::ObstacleCleared { slot, obstacle });
+        }
+
+        crumbled
+    }
+
+    fn special_area(&self, slot: &Slot, special: Special, collectable: &Collectable) -> Vec<Slot> {
+        let mut area: Vec<Slot> = match special {
+            Special::RowClearer => (0..self.width)
+                .map(|column| Slot {
+                    row: slot.row,
+                    column,
+                })
+                .collect(),
+            Special::ColumnClearer => (0..self.height)
+                .map(|row| Slot {
+                    row,
+                    column: slot.column,
+                })
+                .collect(),
+            Special::Bomb => {
+                let mut area = vec![];
+                for column in slot.column.saturating_sub(1)..(slot.column + 2).min(self.width) {
+                    for row in slot.row.saturating_sub(1)..(slot.row + 2).min(self.height) {
+                        area.push(Slot { row, column });
+                    }
+                }
+                area
+            }
+            Special::ColourWipe => self
+                .layout
+                .open_slots()
+                .into_iter()
+                .filter(|target| {
+                    self.has_tile(target) && &self.get(target).collectable == collectable
+                })
+                .collect(),
+        };
+        area.retain(|target| self.has_tile(target));
+
+        area
+    }
+
+    /// Play a complete turn: switch the two slots and resolve all cascades until the grid settles
+    ///
+    /// Returns `None` without touching the grid if the switch is not allowed.
+    pub fn play_switch<R: Rng + ?Sized>(
+        &mut self,
+        one: &Slot,
+        two: &Slot,
+        rng: &mut R,
+    ) -> Option<Vec<BoardEvent>> {
+        if !self.neighbors(one).contains(two) || !self.has_pattern_after_switch(one, two) {
+            return None;
+        }
+        let mut events = vec![self.switch(one, two)];
+        loop {
+            let mut wave = self.take_patterns(rng);
+            if wave.is_empty() {
+                return Some(events);
+            }
+            events.append(&mut wave);
+        }
+    }
+
+    pub fn remove_slots<R: Rng + ?Sized>(
+        &mut self,
+        slots: Vec<Slot>,
+        rng: &mut R,
+    ) -> Vec<BoardEvent> {
+        self.collapse(slots, vec![], rng)
+    }
+
+    /// Smash the slot with a hammer
+    ///
+    /// A chain or stone is broken, ice breaks together with its tile and a plain tile is removed.
+    /// A special tile goes off as if it was matched. The remaining tiles fall down and the grid
+    /// is refilled.
+    pub fn smash<R: Rng + ?Sized>(&mut self, slot: &Slot, rng: &mut R) -> Vec<BoardEvent> {
+        // the waves set off by the smash start a new cascade
+        self.cascade = 0;
+        let mut events = vec![];
+        let obstacle = self.obstacle(slot);
+        if let Some(obstacle) = obstacle {
+            self.obstacles[slot.column][slot.row] = None;
+            events.push(BoardEvent::ObstacleCleared {
+                slot: slot.clone(),
+                obstacle,
+            });
+        }
+        let mut collapsed = match obstacle {
+            // the tile stays where it is, but can be moved again
+            Some(Obstacle::Chain) => vec![],
+            Some(Obstacle::Stone) => self.collapse(vec![], vec![slot.clone()], rng),
+            Some(Obstacle::Ice) | None => {
+                let mut slots = vec![slot.clone()];
+                self.activate_specials(&mut slots, &[], vec![], &mut events);
+                let crumbled = self.break_obstacles(&mut slots, &[], &mut events);
+                self.collapse(slots, crumbled, rng)
+            }
+        };
+        events.append(&mut collapsed);
+
+        events
+    }
+
+    /// Remove the tiles in the given slots, let the remaining tiles fall down and refill the grid
+    ///
+    /// The freed slots are empty already, e.g. because a stone crumbled there.
+    fn collapse<R: Rng + ?Sized>(
+        &mut self,
+        mut slots: Vec<Slot>,
+        freed: Vec<Slot>,
+        rng: &mut R,
+    ) -> Vec<BoardEvent> {
+        slots.sort();
+        slots.dedup();
+
+        let mut events = vec![];
+        let mut empty = vec![];
+        for column_index in 0..self.width {
+            if !slots
+                .iter()
+                .chain(freed.iter())
+                .any(|slot| slot.column == column_index)
+            {
+                continue;
+            }
+            // tiles fall past holes and chained tiles, but stones hold up the tiles above them
+            let segments: Vec<Vec<usize>> = self
+                .layout
+                .open_rows(column_index)
+                .split(|&row| {
+                    !self.has_tile(&Slot {
+                        row,
+                        column: column_index,
+                    })
+                })
+                .map(|segment| {
+                    segment
+                        .iter()
+                        .copied()
+                        .filter(|&row| {
+                            self.is_movable(&Slot {
+                                row,
+                                column: column_index,
+                            })
+                        })
+                        .collect()
+                })
+                .collect();
+            for rows in segments {
+                let mut remaining = vec![];
+                for &row in rows.iter() {
+                    let slot = Slot {
+                        row,
+                        column: column_index,
+                    };
+                    if freed.contains(&slot) {
+                        continue;
+                    }
+                    let tile = self.get(&slot).clone();
+                    if slots.contains(&slot) {
+                        events.push(BoardEvent::Removed {
+                            slot,
+                            collectable: tile.collectable,
+                        });
+                        continue;
+                    }
+                    let target = rows[remaining.len()];
+                    if target != row {
+                        events.push(BoardEvent::Dropped {
+                            from: slot,
+                            to: Slot {
+                                row: target,
+                                column: column_index,
+                            },
+                        });
+                    }
+                    remaining.push(tile);
+                }
+                for (index, &row) in rows.iter().enumerate() {
+                    match remaining.get(index) {
+                        Some(tile) => self.slots[column_index][row] = tile.clone(),
+                        None => empty.push(Slot {
+                            row,
+                            column: column_index,
+                        }),
+                    }
+                }
+            }
+        }
+        events.append(&mut self.refill(empty, rng));
+
+        events
+    }
+
+    /// Put new tiles into the given empty slots
+    ///
+    /// The new tiles queue up in the order of the given slots above the grid or, if the slot is
+    /// below a stone, in the place of that stone.
+    fn refill<R: Rng + ?Sized>(&mut self, empty: Vec<Slot>, rng: &mut R) -> Vec<BoardEvent> {
+        let mut events = vec![];
+        let mut queued = HashMap::new();
+        for slot in empty {
+            let collectable = self.random_collectable(rng);
+            self.slots[slot.column][slot.row] = collectable.clone().into();
+            let top = (slot.row + 1..self.height)
+                .find(|&row| {
+                    let above = Slot {
+                        row,
+                        column: slot.column,
+                    };
+                    self.layout.is_open(&above) && !self.has_tile(&above)
+                })
+                .unwrap_or(self.height);
+            let queue = queued.entry((slot.column, top)).or_insert(0);
+            let drop_height = top + *queue - slot.row;
+            *queue += 1;
+            events.push(BoardEvent::Spawned {
+                slot,
+                collectable,
+                drop_height,
+            });
+        }
+
+        events
+    }
+
+    fn find_patterns_in_columns(&self) -> Vec<Pattern> {
+        (0..self.width)
+            .flat_map(|column| self.find_lines((0..self.height).map(|row| Slot { row, column })))
+            .collect()
+    }
+
+    fn find_patterns_in_rows(&self) -> Vec<Pattern> {
+        (0..self.height)
+            .flat_map(|row| self.find_lines((0..self.width).map(|column| Slot { row, column })))
+            .collect()
+    }
+
+    /// Find all runs of at least three equal collectables in the given sequence of slots
+    ///
+    /// Holes and stones interrupt runs.
+    fn find_lines(&self, slots: impl Iterator<Item = Slot>) -> Vec<Pattern> {
+        let mut lines = vec![];
+        let mut run: Vec<Slot> = vec![];
+        for slot in slots {
+            if !self.has_tile(&slot) {
+                if run.len() >= 3 {
+                    lines.push(Pattern::Line { slots: run });
+                }
+                run = vec![];
+                continue;
+            }
+            if let Some(last) = run.last() {
+                if !self.get(last).matches(self.get(&slot)) {
+                    if run.len() >= 3 {
+                        lines.push(Pattern::Line { slots: run });
+                    }
+                    run = vec![];
+                }
+            }
+            run.push(slot);
+        }
+        if run.len() >= 3 {
+            lines.push(Pattern::Line { slots: run });
+        }
+
+        lines
+    }
+
+    /// Rearrange the tiles so that there is no pattern, but at least one switch that creates one
+    ///
+    /// Returns `None` without touching the grid if no such arrangement was found.
+    pub fn shuffle<R: Rng + ?Sized>(&mut self, rng: &mut R) -> Option<Vec<BoardEvent>> {
+        self.cascade = 0;
+        let original = self.slots.clone();
+        // chained tiles stay where they are
+        let slots: Vec<Slot> = self
+            .layout
+            .open_slots()
+            .into_iter()
+            .filter(|slot| self.is_movable(slot))
+            .collect();
+        for _attempt in 0..SHUFFLE_ATTEMPTS {
+            let mut targets = slots.clone();
+            targets.shuffle(rng);
+            for (from, to) in slots.iter().zip(targets.iter()) {
+                self.slots[to.column][to.row] = original[from.column][from.row].clone();
+            }
+            if self.find_patterns().is_empty() && self.has_possible_switch() {
+                return Some(vec![BoardEvent::Shuffled {
+                    moves: slots.into_iter().zip(targets).collect(),
+                }]);
+            }
+        }
+        self.slots = original;
+
+        None
+    }
+
+    pub fn neighbors(&self, position: &Slot) -> Vec<Slot> {
+        let mut neighbors = vec![];
+        match position {
+            Slot { row: 0, column: 0 } => {
+                neighbors.push(position.walk(1, 0));
+                neighbors.push(position.walk(0, 1));
+            }
+            Slot {
+                row: 0,
+                column: width,
+            } if width == &(self.width - 1) => {
+                neighbors.push(position.walk(1, 0));
+                neighbors.push(position.walk(0, -1));
+            }
+            Slot {
+                row: height,
+                column: width,
+            } if width == &(self.width - 1) && height == &(self.height - 1) => {
+                neighbors.push(position.walk(-1, 0));
+                neighbors.push(position.walk(0, -1));
+            }
+            Slot {
+                row: height,
+                column: 0,
+            } if height == &(self.height - 1) => {
+                neighbors.push(position.walk(-1, 0));
+                neighbors.push(position.walk(0, 1));
+            }
+            Slot { row: 0, column: _ } => {
+                neighbors.push(position.walk(0, 1));
+                neighbors.push(position.walk(0, -1));
+                neighbors.push(position.walk(1, 0));
+            }
+            Slot { row: _, column: 0 } => {
+                neighbors.push(position.walk(1, 0));
+                neighbors.push(position.walk(0, 1));
+                neighbors.push(position.walk(-1, 0));
+            }
+            Slot {
+                row: height,
+                column: _,
+            } if height == &(self.height - 1) => {
+                neighbors.push(position.walk(0, 1));
+                neighbors.push(position.walk(-1, 0));
+                neighbors.push(position.walk(0, -1));
+            }
+            Slot {
+                row: _,
+                column: width,
+            } if width == &(self.width - 1) => {
+                neighbors.push(position.walk(-1, 0));
+                neighbors.push(position.walk(0, -1));
+                neighbors.push(position.walk(1, 0));
+            }
+            Slot {
+                row: height,
+                column: width,
+            } if height < &self.height && width < &self.width => {
+                neighbors.push(position.walk(1, 0));
+                neighbors.push(position.walk(0, 1));
+                neighbors.push(position.walk(-1, 0));
+                neighbors.push(position.walk(0, -1));
+            }
+            slot => panic!("The slot {:?} is outside of the board", slot),
+        };
+        neighbors.retain(|slot| self.has_tile(slot));
+        neighbors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matcher::{Collectable, Obstacle, Pattern, Slot, SpawnWeights, Special, Tile};
+    use crate::simulation::{BoardEvent, Grid, Layout};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn finds_lines_in_rows_on_small_board() {
+        let mut grid = Grid::from_columns(vec![vec![Collectable::Jar; 3]; 3]);
+        grid.slots[1][1].collectable = Collectable::Red;
+
+        assert_eq!(
+            grid.find_patterns_in_rows(),
+            vec![
+                Pattern::Line {
+                    slots: vec![Slot::new(0, 0), Slot::new(0, 1), Slot::new(0, 2)]
+                },
+                Pattern::Line {
+                    slots: vec![Slot::new(2, 0), Slot::new(2, 1), Slot::new(2, 2)]
+                }
+            ]
+        )
+    }
+
+    #[test]
+    fn finds_lines_in_rows() {
+        let size = 5;
+        let mut grid = Grid::from_columns(vec![vec![Collectable::Jar; size]; size]);
+        for index in 0..size {
+            grid.slots[index][index].collectable = Collectable::Red;
+        }
+
+        assert_eq!(
+            grid.find_patterns_in_rows(),
+            vec![
+                Pattern::Line {
+                    slots: vec![
+                        Slot::new(0, 1),
+                        Slot::new(0, 2),
+                        Slot::new(0, 3),
+                        Slot::new(0, 4)
+                    ]
+                },
+                Pattern::Line {
+                    slots: vec![Slot::new(1, 2), Slot::new(1, 3), Slot::new(1, 4)]
+                },
+                Pattern::Line {
+                    slots: vec![Slot::new(3, 0), Slot::new(3, 1), Slot::new(3, 2)]
+                },
+                Pattern::Line {
+                    slots: vec![
+                        Slot::new(4, 0),
+                        Slot::new(4, 1),
+                        Slot::new(4, 2),
+                        Slot::new(4, 3)
+                    ]
+                }
+            ]
+        )
+    }
+
+    #[test]
+    fn combines_crossing_lines_into_shapes() {
+        let mut grid = Grid::from_columns(vec![vec![Collectable::Eye; 5]; 5]);
+        let frogs = [
+            // L shape in the lower left
+            Slot::new(0, 0),
+            Slot::new(1, 0),
+            Slot::new(2, 0),
+            Slot::new(0, 1),
+            Slot::new(0, 2),
+        ];
+        let hearts = [
+            // T shape in the upper right
+            Slot::new(4, 2),
+            Slot::new(4, 3),
+            Slot::new(4, 4),
+            Slot::new(3, 3),
+            Slot::new(2, 3),
+        ];
+        for slot in frogs.iter() {
+            grid.slots[slot.column][slot.row].collectable = Collectable::Frog;
+        }
+        for slot in hearts.iter() {
+            grid.slots[slot.column][slot.row].collectable = Collectable::Heart;
+        }
+        let patterns = grid.find_patterns();
+
+        assert!(patterns.contains(&Pattern::LShape {
+            slots: vec![
+                Slot::new(0, 0),
+                Slot::new(1, 0),
+                Slot::new(2, 0),
+                Slot::new(0, 1),
+                Slot::new(0, 2)
+            ]
+        }));
+        assert!(patterns.contains(&Pattern::TShape {
+            slots: vec![
+                Slot::new(2, 3),
+                Slot::new(3, 3),
+                Slot::new(4, 3),
+                Slot::new(4, 2),
+                Slot::new(4, 4)
+            ]
+        }));
+    }
+
+    #[test]
+    fn combines_all_lines_of_an_h_into_one_shape() {
+        use Collectable::*;
+        let mut grid = Grid::from_columns(vec![
+            vec![Frog, Frog, Frog],
+            vec![Eye, Frog, Heart],
+            vec![Frog, Frog, Frog],
+        ]);
+
+        let patterns = grid.find_patterns();
+
+        assert_eq!(patterns.len(), 1);
+        assert!(matches!(&patterns[0], Pattern::Cluster { slots } if slots.len() == 7));
+        assert_eq!(patterns[0].special(), Some(Special::Bomb));
+        let events = grid.take_patterns(&mut StdRng::seed_from_u64(1));
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| matches!(event, BoardEvent::SpecialCreated { .. }))
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn shapes_with_a_line_of_five_leave_a_colour_wipe() {
+        let line = |slots: Vec<Slot>| Pattern::Line { slots };
+        let long = Pattern::combine(
+            vec![line((0..3).map(|row| Slot::new(row, 2)).collect())],
+            vec![line((0..5).map(|column| Slot::new(0, column)).collect())],
+        );
+        let short = Pattern::combine(
+            vec![line((0..3).map(|row| Slot::new(row, 0)).collect())],
+            vec![line((0..3).map(|column| Slot::new(0, column)).collect())],
+        );
+
+        assert_eq!(long.len(), 1);
+        assert_eq!(long[0].special(), Some(Special::ColourWipe));
+        assert_eq!(short.len(), 1);
+        assert_eq!(short[0].special(), Some(Special::Bomb));
+    }
+
+    #[test]
+    fn correctly_gives_neighbors() {
+        let grid = Grid::from_columns(vec![vec![Collectable::Jar; 5]; 5]);
+        assert_eq!(
+            grid.neighbors(&Slot::new(0, 0)),
+            vec![Slot::new(1, 0), Slot::new(0, 1)]
+        );
+        assert_eq!(
+            grid.neighbors(&Slot::new(0, 3)),
+            vec![Slot::new(0, 4), Slot::new(0, 2), Slot::new(1, 3)]
+        );
+        assert_eq!(
+            grid.neighbors(&Slot::new(2, 3)),
+            vec![
+                Slot::new(3, 3),
+                Slot::new(2, 4),
+                Slot::new(1, 3),
+                Slot::new(2, 2)
+            ]
+        );
+    }
+
+    #[test]
+    fn removed_slots_are_filled_from_above() {
+        let mut grid = Grid::from_columns(vec![
+            vec![Collectable::Eye, Collectable::Frog, Collectable::Heart],
+            vec![Collectable::Jar, Collectable::Jar, Collectable::Jar],
+        ]);
+        let events = grid.remove_slots(vec![Slot::new(0, 0)], &mut StdRng::seed_from_u64(1));
+
+        assert_eq!(
+            events[0],
+            BoardEvent::Removed {
+                slot: Slot::new(0, 0),
+                collectable: Collectable::Eye
+            }
+        );
+        assert_eq!(
+            events[1],
+            BoardEvent::Dropped {
+                from: Slot::new(1, 0),
+                to: Slot::new(0, 0)
+            }
+        );
+        assert_eq!(
+            events[2],
+            BoardEvent::Dropped {
+                from: Slot::new(2, 0),
+                to: Slot::new(1, 0)
+            }
+        );
+        assert!(
+            matches!(events[3], BoardEvent::Spawned { ref slot, drop_height: 1, .. } if slot == &Slot::new(2, 0))
+        );
+        assert_eq!(events.len(), 4);
+        assert_eq!(grid.get(&Slot::new(0, 0)).collectable, Collectable::Frog);
+        assert_eq!(grid.get(&Slot::new(1, 0)).collectable, Collectable::Heart);
+    }
+
+    #[test]
+    fn refuses_switch_without_pattern() {
+        let mut grid = Grid::from_columns(vec![
+            vec![Collectable::Eye, Collectable::Frog, Collectable::Heart],
+            vec![Collectable::Frog, Collectable::Heart, Collectable::Eye],
+            vec![Collectable::Heart, Collectable::Eye, Collectable::Frog],
+        ]);
+
+        assert_eq!(
+            grid.play_switch(
+                &Slot::new(0, 0),
+                &Slot::new(0, 1),
+                &mut StdRng::seed_from_u64(1)
+            ),
+            None
+        );
+        assert_eq!(grid.get(&Slot::new(0, 0)).collectable, Collectable::Eye);
+    }
+
+    #[test]
+    fn counts_waves_of_a_cascade() {
+        let mut grid = Grid::from_columns(vec![
+            vec![
+                Collectable::Eye,
+                Collectable::Frog,
+                Collectable::Eye,
+                Collectable::Eye,
+            ],
+            vec![
+                Collectable::Heart,
+                Collectable::Teeth,
+                Collectable::Frog,
+                Collectable::Heart,
+            ],
+            vec![
+                Collectable::Jar,
+                Collectable::Frog,
+                Collectable::Heart,
+                Collectable::Jar,
+            ],
+        ]);
+        let mut rng = StdRng::seed_from_u64(1);
+        grid.switch(&Slot::new(1, 1), &Slot::new(2, 1));
+        assert_eq!(grid.cascade(), 0);
+
+        // removing the frogs in the second row completes a line of eyes in the first column
+        grid.take_patterns(&mut rng);
+        assert_eq!(grid.cascade(), 1);
+        assert!(!grid.find_patterns().is_empty());
+        grid.take_patterns(&mut rng);
+        assert_eq!(grid.cascade(), 2);
+    }
+
+    #[test]
+    fn line_of_four_leaves_special_tile_in_switched_slot() {
+        let mut grid = Grid::from_columns(vec![
+            vec![Collectable::Eye, Collectable::Frog],
+            vec![Collectable::Eye, Collectable::Heart],
+            vec![Collectable::Frog, Collectable::Eye],
+            vec![Collectable::Eye, Collectable::Heart],
+        ]);
+        grid.switch(&Slot::new(0, 2), &Slot::new(1, 2));
+        let events = grid.take_patterns(&mut StdRng::seed_from_u64(1));
+
+        assert!(events.contains(&BoardEvent::SpecialCreated {
+            slot: Slot::new(0, 2),
+            special: Special::RowClearer
+        }));
+        assert_eq!(
+            grid.get(&Slot::new(0, 2)),
+            &Tile {
+                collectable: Collectable::Eye,
+                special: Some(Special::RowClearer)
+            }
+        );
+    }
+
+    #[test]
+    fn colour_wipe_clears_all_tiles_of_switched_collectable() {
+        let mut grid = Grid::from_columns(vec![
+            vec![Collectable::Eye, Collectable::Frog, Collectable::Heart],
+            vec![Collectable::Frog, Collectable::Heart, Collectable::Frog],
+            vec![Collectable::Heart, Collectable::Eye, Collectable::Jar],
+        ]);
+        grid.slots[0][0].special = Some(Special::ColourWipe);
+        assert!(grid.has_pattern_after_switch(&Slot::new(0, 0), &Slot::new(0, 1)));
+
+        grid.switch(&Slot::new(0, 0), &Slot::new(0, 1));
+        let events = grid.take_patterns(&mut StdRng::seed_from_u64(1));
+        let removed: Vec<&Slot> = events
+            .iter()
+            .filter_map(|event| match event {
+                BoardEvent::Removed { slot, .. } => Some(slot),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            removed,
+            vec![
+                &Slot::new(0, 0),
+                &Slot::new(1, 0),
+                &Slot::new(0, 1),
+                &Slot::new(2, 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn shuffle_leaves_a_switch_without_patterns() {
+        use Collectable::*;
+        let mut grid = Grid::from_columns(vec![
+            vec![Eye, Frog, Heart, Eye],
+            vec![Frog, Heart, Eye, Frog],
+            vec![Heart, Eye, Frog, Heart],
+            vec![Eye, Frog, Heart, Eye],
+        ]);
+        assert!(!grid.has_possible_switch());
+
+        let events = grid
+            .shuffle(&mut StdRng::seed_from_u64(1))
+            .expect("There is an arrangement with a possible switch");
+
+        assert!(matches!(events[..], [BoardEvent::Shuffled { .. }]));
+        assert!(grid.find_patterns().is_empty());
+        assert!(grid.has_possible_switch());
+        let eyes = grid
+            .slots
+            .iter()
+            .flatten()
+            .filter(|tile| tile.collectable == Eye)
+            .count();
+        assert_eq!(eyes, 6);
+    }
+
+    #[test]
+    fn best_switch_prefers_given_collectables() {
+        use Collectable::*;
+        let mut grid = Grid::from_columns(vec![
+            vec![Eye, Heart, Jar],
+            vec![Eye, Heart, Teeth],
+            vec![Frog, Spider, Jar],
+            vec![Eye, Heart, Teeth],
+        ]);
+
+        assert_eq!(
+            grid.best_switch(&[]),
+            Some((Slot::new(0, 2), Slot::new(0, 3)))
+        );
+        assert_eq!(
+            grid.best_switch(&[Heart]),
+            Some((Slot::new(1, 2), Slot::new(1, 3)))
+        );
+    }
+
+    #[test]
+    fn collectables_without_weight_never_spawn() {
+        let (grid, events) = Grid::new(
+            Layout::rectangle(8, 9),
+            vec![Collectable::Eye, Collectable::Frog, Collectable::Red],
+            SpawnWeights::new(vec![(Collectable::Red, 0.)]),
+            &mut StdRng::seed_from_u64(1),
+        );
+
+        assert_eq!(events.len(), 72);
+        for column in 0..8 {
+            for row in 0..9 {
+                assert_ne!(
+                    grid.get(&Slot::new(row, column)).collectable,
+                    Collectable::Red
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn tiles_fall_past_holes() {
+        let layout = Layout::try_from(vec!["oo".to_owned(), ".o".to_owned(), "oo".to_owned()])
+            .expect("The layout is valid");
+        let (mut grid, _) = Grid::new(
+            layout,
+            vec![Collectable::Jar],
+            SpawnWeights::default(),
+            &mut StdRng::seed_from_u64(1),
+        );
+        assert_eq!(grid.neighbors(&Slot::new(0, 0)), vec![Slot::new(0, 1)]);
+
+        let events = grid.remove_slots(vec![Slot::new(0, 0)], &mut StdRng::seed_from_u64(1));
+
+        assert_eq!(
+            events,
+            vec![
+                BoardEvent::Removed {
+                    slot: Slot::new(0, 0),
+                    collectable: Collectable::Jar
+                },
+                BoardEvent::Dropped {
+                    from: Slot::new(2, 0),
+                    to: Slot::new(0, 0)
+                },
+                BoardEvent::Spawned {
+                    slot: Slot::new(2, 0),
+                    collectable: Collectable::Jar,
+                    drop_height: 1
+                }
+            ]
+        );
+    }
+
+    fn grid_with_obstacles(rows: &[&str], columns: Vec<Vec<Collectable>>) -> Grid {
+        let layout = Layout::try_from(rows.iter().map(|row| row.to_string()).collect::<Vec<_>>())
+            .expect("The layout is valid");
+        let (mut grid, _) = Grid::new(
+            layout,
+            vec![Collectable::Jar],
+            SpawnWeights::default(),
+            &mut StdRng::seed_from_u64(1),
+        );
+        grid.slots = columns
+            .into_iter()
+            .map(|column| column.into_iter().map(Tile::from).collect())
+            .collect();
+        grid
+    }
+
+    #[test]
+    fn matching_chained_tile_only_breaks_the_chain() {
+        use Collectable::*;
+        let mut grid = grid_with_obstacles(
+            &["ooo", "ooo", "oco"],
+            vec![
+                vec![Eye, Frog, Heart],
+                vec![Eye, Heart, Frog],
+                vec![Eye, Spider, Jar],
+            ],
+        );
+        assert!(!grid.has_pattern_after_switch(&Slot::new(0, 1), &Slot::new(1, 1)));
+
+        let events = grid.take_patterns(&mut StdRng::seed_from_u64(1));
+
+        assert!(events.contains(&BoardEvent::ObstacleCleared {
+            slot: Slot::new(0, 1),
+            obstacle: Obstacle::Chain
+        }));
+        assert!(!events.iter().any(
+            |event| matches!(event, BoardEvent::Removed { slot, .. } if slot == &Slot::new(0, 1))
+        ));
+        assert_eq!(grid.get(&Slot::new(0, 1)).collectable, Eye);
+        assert_eq!(grid.obstacle(&Slot::new(0, 1)), None);
+        assert_eq!(grid.get(&Slot::new(0, 0)).collectable, Frog);
+    }
+
+    #[test]
+    fn tiles_fall_past_chained_tiles() {
+        use Collectable::*;
+        let mut grid =
+            grid_with_obstacles(&["o", "o", "c", "o"], vec![vec![Eye, Frog, Heart, Spider]]);
+
+        let events = grid.remove_slots(vec![Slot::new(0, 0)], &mut StdRng::seed_from_u64(1));
+
+        assert!(events.contains(&BoardEvent::Dropped {
+            from: Slot::new(2, 0),
+            to: Slot::new(0, 0)
+        }));
+        assert!(events.contains(&BoardEvent::Dropped {
+            from: Slot::new(3, 0),
+            to: Slot::new(2, 0)
+        }));
+        assert!(events.contains(&BoardEvent::Spawned {
+            slot: Slot::new(3, 0),
+            collectable: Jar,
+            drop_height: 1
+        }));
+        assert_eq!(grid.get(&Slot::new(0, 0)).collectable, Heart);
+        assert_eq!(grid.get(&Slot::new(1, 0)).collectable, Frog);
+        assert_eq!(grid.obstacle(&Slot::new(1, 0)), Some(Obstacle::Chain));
+        assert_eq!(grid.get(&Slot::new(2, 0)).collectable, Spider);
+    }
+
+    #[test]
+    fn matching_chained_special_tile_does_not_set_it_off() {
+        use Collectable::*;
+        let mut grid = grid_with_obstacles(
+            &["ooo", "ooo", "oco"],
+            vec![
+                vec![Eye, Frog, Heart],
+                vec![Eye, Heart, Frog],
+                vec![Eye, Spider, Jar],
+            ],
+        );
+        grid.slots[1][0].special = Some(Special::ColumnClearer);
+
+        let events = grid.take_patterns(&mut StdRng::seed_from_u64(1));
+
+        assert!(!events
+            .iter()
+            .any(|event| matches!(event, BoardEvent::Activated { .. })));
+        assert!(events.contains(&BoardEvent::ObstacleCleared {
+            slot: Slot::new(0, 1),
+            obstacle: Obstacle::Chain
+        }));
+        assert_eq!(
+            grid.get(&Slot::new(0, 1)).special,
+            Some(Special::ColumnClearer)
+        );
+        assert_eq!(grid.get(&Slot::new(1, 1)).collectable, Heart);
+    }
+
+    #[test]
+    fn stones_hold_up_tiles_and_crumble_next_to_matches() {
+        use Collectable::*;
+        let mut grid = grid_with_obstacles(
+            &["#oo", "oo#", "ooo"],
+            vec![
+                vec![Eye, Frog, Jar],
+                vec![Eye, Heart, Spider],
+                vec![Eye, Jar, Jar],
+            ],
+        );
+
+        let events = grid.take_patterns(&mut StdRng::seed_from_u64(1));
+
+        assert!(events.contains(&BoardEvent::ObstacleCleared {
+            slot: Slot::new(1, 2),
+            obstacle: Obstacle::Stone
+        }));
+        assert_eq!(grid.obstacle(&Slot::new(2, 0)), Some(Obstacle::Stone));
+        // the stone in the first column is not next to the match and keeps the new tile below it
+        assert!(events.contains(&BoardEvent::Dropped {
+            from: Slot::new(1, 0),
+            to: Slot::new(0, 0)
+        }));
+        assert!(events.iter().any(|event| matches!(
+            event,
+            BoardEvent::Spawned { slot, drop_height: 1, .. } if slot == &Slot::new(1, 0)
+        )));
+        assert!(events.contains(&BoardEvent::Dropped {
+            from: Slot::new(2, 2),
+            to: Slot::new(0, 2)
+        }));
+        assert_eq!(grid.get(&Slot::new(0, 2)).collectable, Jar);
+    }
+
+    #[test]
+    fn smashing_a_stone_lets_the_tiles_above_fall() {
+        use Collectable::*;
+        let mut grid = grid_with_obstacles(&["o", "#", "o"], vec![vec![Frog, Eye, Heart]]);
+
+        let events = grid.smash(&Slot::new(1, 0), &mut StdRng::seed_from_u64(1));
+
+        assert!(events.contains(&BoardEvent::ObstacleCleared {
+            slot: Slot::new(1, 0),
+            obstacle: Obstacle::Stone
+        }));
+        assert!(events.contains(&BoardEvent::Dropped {
+            from: Slot::new(2, 0),
+            to: Slot::new(1, 0)
+        }));
+        assert_eq!(grid.obstacle(&Slot::new(1, 0)), None);
+        assert_eq!(grid.get(&Slot::new(0, 0)).collectable, Frog);
+        assert_eq!(grid.get(&Slot::new(1, 0)).collectable, Heart);
+    }
+
+    #[test]
+    fn smashing_a_special_tile_activates_it() {
+        use Collectable::*;
+        let mut grid = Grid::from_columns(vec![
+            vec![Eye, Frog, Heart],
+            vec![Heart, Eye, Frog],
+            vec![Frog, Heart, Eye],
+        ]);
+        grid.slots[1][1].special = Some(Special::RowClearer);
+
+        let events = grid.smash(&Slot::new(1, 1), &mut StdRng::seed_from_u64(1));
+
+        assert!(events.contains(&BoardEvent::Activated {
+            slot: Slot::new(1, 1),
+            special: Special::RowClearer
+        }));
+        for column in 0..3 {
+            assert!(events.iter().any(|event| matches!(
+                event,
+                BoardEvent::Removed { slot, .. } if slot == &Slot::new(1, column)
+            )));
+        }
+    }
+
+    #[test]
+    fn smashing_starts_a_new_cascade() {
+        use Collectable::*;
+        let mut grid = Grid::from_columns(vec![
+            vec![Eye, Frog, Eye, Eye],
+            vec![Heart, Teeth, Frog, Heart],
+            vec![Jar, Frog, Heart, Jar],
+        ]);
+        let mut rng = StdRng::seed_from_u64(1);
+        grid.switch(&Slot::new(1, 1), &Slot::new(2, 1));
+        grid.take_patterns(&mut rng);
+        grid.take_patterns(&mut rng);
+        assert_eq!(grid.cascade(), 2);
+
+        grid.smash(&Slot::new(0, 0), &mut rng);
+
+        assert_eq!(grid.cascade(), 0);
+    }
+}
@@ -0,0 +1,90 @@
+//! Persist small amounts of data between sessions
+//!
+//! Values are stored as RON in the user's data directory on native platforms and in the
+//! browser's local storage on the web.
+
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Load the value that was last saved under the given key
+pub fn load<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let serialized = read(key)?;
+    match ron::de::from_str(&serialized) {
+        Ok(value) => Some(value),
+        Err(error) => {
+            warn!("Failed to read saved '{}': {}", key, error);
+            None
+        }
+    }
+}
+
+pub fn save<T: Serialize>(key: &str, value: &T) {
+    match ron::ser::to_string_pretty(value, Default::default()) {
+        Ok(serialized) => write(key, &serialized),
+        Err(error) => warn!("Failed to serialize '{}': {}", key, error),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read(key: &str) -> Option<String> {
+    std::fs::read_to_string(data_directory()?.join(format!("{}.ron", key))).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(key: &str, serialized: &str) {
+    let directory = match data_directory() {
+        Some(directory) => directory,
+        None => {
+            warn!("Could not find a directory to save '{}' in", key);
+            return;
+        }
+    };
+    if let Err(error) = std::fs::create_dir_all(&directory)
+        .and_then(|_| std::fs::write(directory.join(format!("{}.ron", key)), serialized))
+    {
+        warn!("Failed to save '{}': {}", key, error);
+    }
+}
+
+/// `$XDG_DATA_HOME/wicked_potions` with the usual fallbacks for each platform
+#[cfg(not(target_arch = "wasm32"))]
+fn data_directory() -> Option<std::path::PathBuf> {
+    use std::env::var_os;
+    use std::path::PathBuf;
+
+    let base = if let Some(data_home) = var_os("XDG_DATA_HOME") {
+        PathBuf::from(data_home)
+    } else if cfg!(target_os = "windows") {
+        PathBuf::from(var_os("APPDATA")?)
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(var_os("HOME")?).join("Library/Application Support")
+    } else {
+        PathBuf::from(var_os("HOME")?).join(".local/share")
+    };
+
+    Some(base.join("wicked_potions"))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read(key: &str) -> Option<String> {
+    local_storage()?.get_item(&storage_key(key)).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(key: &str, serialized: &str) {
+    let stored = local_storage().map(|storage| storage.set_item(&storage_key(key), serialized));
+    if !matches!(stored, Some(Ok(()))) {
+        warn!("Failed to save '{}' in the local storage", key);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+fn storage_key(key: &str) -> String {
+    format!("wicked_potions.{}", key)
+}
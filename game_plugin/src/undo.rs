@@ -0,0 +1,237 @@
+use crate::animate::Move;
+use crate::board::{Board, Cauldron, ObstacleMaterials, PlayerSwitch, RunClock, Score, Selected};
+use crate::loading::{FontAssets, TextureAssets};
+use crate::matcher::{Collectable, Obstacle, Slot};
+use crate::menu::ButtonMaterials;
+use crate::mode::{RunLimit, RunSettings};
+use crate::random::GameRng;
+use crate::replay::Replays;
+use crate::simulation::Grid;
+use crate::{GameState, SystemLabels};
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+
+pub struct UndoPlugin;
+
+/// This plugin takes back switches in runs that allow it, like the practice mode
+/// The board, cauldron, score, clock, limit and random generators are remembered whenever the
+/// board comes to rest.
+/// U, Backspace, the west button of a gamepad or the undo button restore them to how they were
+/// before the last switch.
+impl Plugin for UndoPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(History::default())
+            .add_event::<TurnUndone>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(setup_undo.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(remember_turn.system().after(SystemLabels::UserInput))
+                    .with_system(undo_turn.system().label(SystemLabels::ReadInput)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing).with_system(remove_undo.system()),
+            );
+    }
+}
+
+/// The last switch was taken back and the state of the run was restored
+pub struct TurnUndone;
+
+struct Snapshot {
+    grid: Grid,
+    cauldron: Cauldron,
+    score: Score,
+    clock: RunClock,
+    limit: RunLimit,
+    /// The generators of the board and the recipes, so a switch taken back plays out the same
+    board_rng: StdRng,
+    recipe_rng: StdRng,
+}
+
+#[derive(Default)]
+struct History {
+    /// Taken when the board came to rest for the last time
+    rest: Option<Snapshot>,
+    /// The state before each switch, the last switch last
+    turns: Vec<Snapshot>,
+}
+
+struct UndoUi;
+
+struct UndoButton;
+
+fn setup_undo(
+    mut commands: Commands,
+    settings: Res<RunSettings>,
+    font_assets: Res<FontAssets>,
+    button_materials: Res<ButtonMaterials>,
+    mut history: ResMut<History>,
+) {
+    *history = History::default();
+    if !settings.undo {
+        return;
+    }
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(90.0), Val::Px(32.0)),
+                margin: Rect::all(Val::Auto),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        })
+        .insert(UndoButton)
+        .insert(UndoUi)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text {
+                        sections: vec![TextSection {
+                            value: "Undo".to_string(),
+                            style: TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 20.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        }],
+                        alignment: Default::default(),
+                    },
+                    ..Default::default()
+                })
+                .insert(UndoUi);
+        });
+}
+
+/// Remember the state before every switch
+///
+/// Switches made before the board came to rest again are taken back together with the one before.
+fn remember_turn(
+    settings: Res<RunSettings>,
+    board: Res<Board>,
+    cauldron: Res<Cauldron>,
+    score: Res<Score>,
+    clock: Res<RunClock>,
+    limit: Res<RunLimit>,
+    rng: Res<GameRng>,
+    moving: Query<Entity, With<Vec<Move>>>,
+    mut switches: EventReader<PlayerSwitch>,
+    mut history: ResMut<History>,
+) {
+    if !settings.undo {
+        return;
+    }
+    if switches.iter().count() > 0 {
+        if let Some(rest) = history.rest.take() {
+            history.turns.push(rest);
+        }
+        return;
+    }
+    // collectables on their way into the cauldron don't count as animating the board
+    let at_rest = !board.animating && board.grid.is_settled() && moving.iter().next().is_none();
+    if !at_rest {
+        if history.rest.is_some() {
+            history.rest = None;
+        }
+    } else if history.rest.is_none() {
+        history.rest = Some(Snapshot {
+            grid: board.grid.clone(),
+            cauldron: cauldron.clone(),
+            score: score.clone(),
+            clock: clock.clone(),
+            limit: limit.clone(),
+            board_rng: rng.board.clone(),
+            recipe_rng: rng.recipes.clone(),
+        });
+    }
+}
+
+fn undo_turn(
+    mut commands: Commands,
+    settings: Res<RunSettings>,
+    replays: Res<Replays>,
+    input: (
+        Res<Input<KeyCode>>,
+        Res<Gamepads>,
+        Res<Input<GamepadButton>>,
+    ),
+    button_materials: Res<ButtonMaterials>,
+    mut interaction_query: Query<
+        (&Interaction, &mut Handle<ColorMaterial>),
+        (Changed<Interaction>, With<UndoButton>),
+    >,
+    textures: Res<TextureAssets>,
+    obstacle_materials: Res<ObstacleMaterials>,
+    tiles: Query<Entity, (With<Collectable>, Or<(With<Slot>, With<Vec<Move>>)>)>,
+    obstacles: Query<Entity, With<Obstacle>>,
+    mut history: ResMut<History>,
+    mut board: ResMut<Board>,
+    run: (
+        ResMut<Cauldron>,
+        ResMut<Score>,
+        ResMut<RunClock>,
+        ResMut<RunLimit>,
+        ResMut<GameRng>,
+    ),
+    mut selection: ResMut<Selected>,
+    mut undone: EventWriter<TurnUndone>,
+) {
+    if !settings.undo || replays.is_playing() {
+        return;
+    }
+    let mut clicked = false;
+    for (interaction, mut material) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => clicked = true,
+            Interaction::Hovered => *material = button_materials.hovered.clone(),
+            Interaction::None => *material = button_materials.normal.clone(),
+        }
+    }
+    let (keys, gamepads, gamepad_buttons) = input;
+    let pressed = clicked
+        || keys.just_pressed(KeyCode::U)
+        || keys.just_pressed(KeyCode::Back)
+        || gamepads.iter().any(|gamepad| {
+            gamepad_buttons.just_pressed(GamepadButton(*gamepad, GamepadButtonType::West))
+        });
+    // a cascade has to be played out before it can be taken back
+    if !pressed || board.animating || !board.grid.is_settled() {
+        return;
+    }
+    let snapshot = match history.turns.pop() {
+        Some(snapshot) => snapshot,
+        None => return,
+    };
+
+    // this includes the collectables on their way into the cauldron, but not the ones in the HUD
+    for entity in tiles.iter().chain(obstacles.iter()) {
+        commands.entity(entity).despawn();
+    }
+    board.restore(snapshot.grid, &mut commands, &textures, &obstacle_materials);
+    let (mut cauldron, mut score, mut clock, mut limit, mut rng) = run;
+    *cauldron = snapshot.cauldron;
+    *score = snapshot.score;
+    *clock = snapshot.clock;
+    *limit = snapshot.limit;
+    rng.board = snapshot.board_rng;
+    rng.recipes = snapshot.recipe_rng;
+    *selection = None;
+    history.rest = None;
+    undone.send(TurnUndone);
+}
+
+fn remove_undo(mut commands: Commands, elements: Query<Entity, With<UndoUi>>) {
+    for entity in elements.iter() {
+        commands.entity(entity).despawn();
+    }
+}